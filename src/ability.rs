@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use stopwatch::Stopwatch;
+use sdl2::rect::{Point, Rect};
+use strum_macros::EnumString;
+
+use crate::combat;
+
+//Energy regenerated per frame by everything with max_energy above 0
+pub const ENERGY_REGEN: f32 = 0.03;
+pub const BOMBARD_DAMAGE: i32 = 40;
+pub const NANOREPAIR_HEAL: i32 = 30;
+//Pixels around the caster's collider that Nanorepair reaches
+pub const NANOREPAIR_RANGE: f32 = 200.0;
+
+//What the player has to click after pressing the ability's button
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Targeting {
+    Instant,
+    Unit,
+    Point,
+    Area(u32), // Radius in pixels
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, EnumString)]
+pub enum AbilityKind {
+    Move,
+    Attack,
+    Bombard,
+    Nanorepair,
+}
+
+#[allow(unreachable_patterns)]
+impl AbilityKind {
+    pub fn get_targeting<'f>(&'f self) -> Targeting {
+        match self {
+            AbilityKind::Move => { Targeting::Point },
+            AbilityKind::Attack => { Targeting::Unit },
+            AbilityKind::Bombard => { Targeting::Area(50) },
+            AbilityKind::Nanorepair => { Targeting::Instant },
+            _ => { unimplemented!() }
+        }
+    }
+
+    pub fn get_cooldown<'f>(&'f self) -> Duration {
+        match self {
+            AbilityKind::Move => { Duration::ZERO },
+            AbilityKind::Attack => { Duration::ZERO },
+            AbilityKind::Bombard => { Duration::from_millis(3000) },
+            AbilityKind::Nanorepair => { Duration::from_millis(10000) },
+            _ => { unimplemented!() }
+        }
+    }
+
+    pub fn get_energy_cost<'f>(&'f self) -> f32 {
+        match self {
+            AbilityKind::Move => { 0.0 },
+            AbilityKind::Attack => { 0.0 },
+            AbilityKind::Bombard => { 75.0 },
+            AbilityKind::Nanorepair => { 50.0 },
+            _ => { unimplemented!() }
+        }
+    }
+
+    //Pixels between the caster's collider and the target, None for no limit
+    pub fn get_range<'f>(&'f self) -> Option<f32> {
+        match self {
+            AbilityKind::Move => { None },
+            AbilityKind::Attack => { None },
+            AbilityKind::Bombard => { Some(250.0) },
+            AbilityKind::Nanorepair => { None },
+            _ => { unimplemented!() }
+        }
+    }
+
+    pub fn get_texture_index<'f>(&'f self) -> i32 {
+        match self {
+            AbilityKind::Move => { 10 },
+            AbilityKind::Attack => { 11 },
+            AbilityKind::Bombard => { 12 },
+            AbilityKind::Nanorepair => { 13 },
+            _ => { unimplemented!() }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CastTarget {
+    None,
+    Entity(u32),
+    Point(Point),
+}
+
+//An ability waiting for its caster to get in range
+#[derive(Clone, Copy)]
+pub struct Cast {
+    pub ability: AbilityKind,
+    pub target: CastTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastError {
+    Cooldown,
+    NotEnoughEnergy,
+    InvalidTarget,
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CastError::Cooldown => { write!(f, "ability is on cooldown") },
+            CastError::NotEnoughEnergy => { write!(f, "not enough energy") },
+            CastError::InvalidTarget => { write!(f, "invalid target") },
+        }
+    }
+}
+
+impl std::error::Error for CastError {}
+
+//Energy and cooldowns of one unit or building
+#[derive(Clone)]
+pub struct Caster {
+    pub energy: f32,
+    pub max_energy: f32,
+    pub pending: Option<Cast>,
+    cooldowns: HashMap<AbilityKind, Stopwatch>,
+}
+
+impl Caster {
+    pub fn new(energy: f32, max_energy: f32) -> Caster {
+        Caster {
+            energy,
+            max_energy,
+            pending: None,
+            cooldowns: HashMap::new(),
+        }
+    }
+
+    pub fn regenerate<'f>(&'f mut self) {
+        self.energy = (self.energy + ENERGY_REGEN).min(self.max_energy);
+    }
+
+    pub fn can_cast<'f>(&'f self, ability: AbilityKind) -> Result<(), CastError> {
+        if let Some(timer) = self.cooldowns.get(&ability) {
+            if timer.elapsed() < ability.get_cooldown() {
+                return Err(CastError::Cooldown);
+            }
+        }
+        if self.energy < ability.get_energy_cost() {
+            return Err(CastError::NotEnoughEnergy);
+        }
+        Ok(())
+    }
+
+    //Pays the energy and starts the cooldown
+    pub fn spend<'f>(&'f mut self, ability: AbilityKind) -> Result<(), CastError> {
+        self.can_cast(ability)?;
+        self.energy -= ability.get_energy_cost();
+        self.cooldowns.insert(ability, Stopwatch::start_new());
+        Ok(())
+    }
+}
+
+pub fn in_range(ability: AbilityKind, caster: Rect, target: Rect) -> bool {
+    match ability.get_range() {
+        Some(range) => { combat::edge_distance(caster, target) <= range },
+        None => { true }
+    }
+}
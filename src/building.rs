@@ -1,23 +1,33 @@
 use std::time::Duration;
-use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::fmt;
 
 use stopwatch::Stopwatch;
 use sdl2::rect::{Point, Rect};
 use sdl2::render::WindowCanvas;
 use strum::IntoEnumIterator;
-use strum_macros::{EnumIter, Display};
+use strum_macros::{EnumIter, Display, EnumString};
 
 use crate::general::{Selectable, Renderable};
 use crate::sprite::{TextureType, TextureManager};
 use crate::world::{World, Cell};
+use crate::unit::{Unit, UnitType};
+use crate::combat::{self, Weapon, Target, Shot};
+use crate::ability::{self, AbilityKind, Caster, Cast, CastTarget, CastError};
+use crate::resources::{ResourceKind, Cost};
+use crate::stats;
+use crate::cards;
 
 use super::sprite::Sprite;
 use super::general::{self, Collidable, Faction};
 use super::ui::{Button, UiElement, ButtonFunction};
 use super::Player;
 
+pub const BUILD_RANGE: f32 = 10.0; // Pixels between a worker and the site it builds
+
 #[derive(Clone)]
 pub struct Building {
+    pub id: u32,
     pub sprite: Sprite,
     pub team: i32,
     pub building_type: BuildingType,
@@ -26,10 +36,19 @@ pub struct Building {
     pub collider: Rect,
     pub buttons: Vec<[Option<Button>; 16]>,
     pub button_panel_index: usize,
-    pub button_panel_limit: usize,
-    pub constructing: Option<Construction>,
+    pub button_panel_limit: usize, // Panels of its own card, the construction card follows
+    pub flying_panel: usize, // First panel of the card shown while airborne
+    pub production_queue: VecDeque<Construction>,
     pub status: BuildingStatus,
-    pub place_construction_flag: bool,
+    pub build_timer: Stopwatch, // Only runs while a worker is building it
+    pub hp: i32,
+    pub max_hp: i32,
+    pub armor: i32,
+    pub weapon: Option<Weapon>,
+    pub caster: Caster,
+    pub rally: Option<Rally>, // Where trained units walk to
+    pub position: (f32, f32), // Top left corner, only changes while flying
+    pub move_target: Option<Point>, // Top left corner a flying building heads for
 }
 
 impl Building {
@@ -38,18 +57,29 @@ impl Building {
             atlas: &'f TextureManager) -> Building {
         
         let mut new_building = Building {
+            id: general::next_entity_id(),
             team,
             building_type,
             faction,
             buttons: vec![],
             button_panel_index: 0,
-            button_panel_limit: 1,
-            place_construction_flag: false,
+            button_panel_limit: 0,
+            flying_panel: 0,
             status: BuildingStatus::NotBuilt,
+            build_timer: Stopwatch::new(),
             collider_type: Collidable::GroundCollidable,
             collider: Rect::new(location.x, location.y,
                 building_type.get_w(), building_type.get_h()),
-            constructing: None,
+            production_queue: VecDeque::new(),
+            rally: None,
+            position: (location.x as f32, location.y as f32),
+            move_target: None,
+            hp: building_type.get_max_hp(),
+            max_hp: building_type.get_max_hp(),
+            armor: building_type.get_armor(),
+            weapon: building_type.get_weapon(),
+            caster: Caster::new(building_type.get_max_energy() / 4.0,
+                building_type.get_max_energy()),
             sprite: {
                 Sprite::new(
                     Rect::new(location.x, location.y,
@@ -64,45 +94,25 @@ impl Building {
         return new_building;
     }
 
+    //The building's own card, followed by the ones it shows until it's finished
+    //and while it's airborne
     fn init_buttons<'f>(building: &'f mut Building, bottom_right_ui: Vec<UiElement>,
             atlas: &'f TextureManager) {
-
-        match building.faction {
-            Faction::PlaceholderFaction1 => {
-                match building.building_type {
-                    BuildingType::CommandCentre => {
-                        building.button_panel_limit = 3;
-                        building.buttons.push([None; 16]);
-                        building.buttons.push([None; 16]);
-                        building.buttons.push([None; 16]);
-                        
-                        building.buttons[0][0] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::ShowTier1Buildings, 0);
-                        building.buttons[0][1] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::ShowTier2Buildings, 1);
-                        building.buttons[0][15] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::PlaceConstruction, 15);
-                       
-                        building.buttons[1][0] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::MakeCC, 0);
-                        building.buttons[1][1] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::MakeBarracks, 1);
-                        building.buttons[1][15] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::Back, 15);
-
-                        building.buttons[2][15] = general::gen_button(atlas,
-                            bottom_right_ui.to_owned(), ButtonFunction::Back, 15);
-                    },
-                    BuildingType::Barracks => {
-                        building.button_panel_limit = 1;
-                        building.buttons.push([None; 16]);
-
-                    }
-                }
-            }
-        } 
+        building.buttons = general::gen_panels(atlas, &bottom_right_ui,
+            cards::building(building.building_type));
+        building.button_panel_limit = building.buttons.len();
+        building.buttons.extend(general::gen_panels(atlas, &bottom_right_ui,
+            cards::construction()));
+        building.flying_panel = building.buttons.len();
+        building.buttons.extend(general::gen_panels(atlas, &bottom_right_ui,
+            cards::flying()));
     }
     
+    pub fn has_ability<'f>(&'f self, ability: AbilityKind) -> bool {
+        self.buttons.iter().flatten().flatten()
+            .any(|button| button.btn_function == ButtonFunction::Ability(ability))
+    }
+
     pub fn _get_texture_type<'f>(&'f self) -> TextureType {
         TextureType::Building { faction: self.faction, b_type: self.building_type }
     }
@@ -123,80 +133,288 @@ impl Building {
         self.collider.y / 25
     }
 
-    pub fn execute_fn<'f>(&'f mut self, function: ButtonFunction, owner: Player) {
-        match function {
-            ButtonFunction::ShowTier1Buildings => {
-                self.set_button_panel(1); 
-            },
-            ButtonFunction::ShowTier2Buildings => {
-                self.set_button_panel(2);
-            },
-            ButtonFunction::Back => {
-                self.set_button_panel(0);
-            },
-            ButtonFunction::MakeWorker => {
-                unimplemented!();
-            },
-            ButtonFunction::MakeBarracks => {
-                self.start_construction(BuildingType::Barracks,
-                    owner.bottom_right_ui.to_owned());
-            },
-            ButtonFunction::MakeCC => {
-                self.start_construction(BuildingType::CommandCentre,
-                    owner.bottom_right_ui.to_owned());
+    //Returns false if the action didn't go through, like queueing into a full queue
+    pub fn execute_fn<'f>(&'f mut self, function: ButtonFunction, owner: Player) -> bool {
+        let product = match function {
+            ButtonFunction::MakeWorker => { Some(ConstructionType::Unit(UnitType::Worker)) },
+            ButtonFunction::MakeMarine => { Some(ConstructionType::Unit(UnitType::Marine)) },
+            ButtonFunction::MakeWraith => { Some(ConstructionType::Unit(UnitType::Wraith)) },
+            ButtonFunction::Stop => {
+                self.stop();
+                None
             },
-            ButtonFunction::PlaceConstruction => {
-                self.place_construction_flag = true; 
+            _ => { None }
+        };
+
+        match product {
+            Some(product) => {
+                self.start_construction(product, owner.bottom_right_ui.to_owned())
             },
-            _ => {}
-        } 
+            None => { true }
+        }
     }
     
     pub fn construction_done<'f>(&'f self) -> bool {
-        if self.constructing.is_some() {
-            let check: bool = self.constructing.to_owned().unwrap().check_timer();
-            return check;
+        match self.production_queue.front() {
+            Some(construction) => { construction.check_timer() },
+            None => { false }
+        }
+    }
+    
+    //Removes the finished front of the queue and starts on the next item
+    pub fn take_constructed<'f>(&'f mut self) -> Construction {
+        let completed_con = self.production_queue.pop_front().unwrap();
+        self.start_next_construction();
+        return completed_con;
+    }
+
+    pub fn cancel_construction<'f>(&'f mut self, index: usize) -> Option<Construction> {
+        let cancelled = self.production_queue.remove(index);
+        if index == 0 {
+            self.start_next_construction();
+        }
+        cancelled
+    }
+
+    fn start_next_construction<'f>(&'f mut self) {
+        if let Some(next) = self.production_queue.front_mut() {
+            if !next.timer.is_running() {
+                next.timer.start();
+            }
+        }
+    }
+
+    //Sites being built and finished buildings stand on the grid, ghosts and sites
+    //still waiting for their worker don't
+    pub fn on_grid<'f>(&'f self) -> bool {
+        matches!(self.status, BuildingStatus::Built | BuildingStatus::UnderConstruction { .. })
+    }
+
+    pub fn is_airborne<'f>(&'f self) -> bool {
+        matches!(self.status, BuildingStatus::Flying | BuildingStatus::Landing)
+    }
+
+    //Done being built, whether it stands on the ground or not
+    pub fn is_finished<'f>(&'f self) -> bool {
+        self.status == BuildingStatus::Built || self.is_airborne()
+    }
+
+    pub fn construction_progress<'f>(&'f self) -> Option<f32> {
+        match self.status {
+            BuildingStatus::UnderConstruction { progress } => Some(progress),
+            _ => None,
+        }
+    }
+
+    //Frees what the building holds on the grid, a refinery's geyser keeps blocking its cells
+    pub fn release_footprint<'f>(&'f self, game_map: &'f mut World) {
+        if !self.on_grid() {
+            return;
+        }
+
+        if self.building_type.requires_geyser() {
+            if let Some(id) = game_map.resource_at(self.collider.center()) {
+                game_map.set_extractor(id, None);
+            }
+        } else {
+            game_map.set_occupied(self.collider, false);
+        }
+    }
+
+    //The foundation goes down with a tenth of the hp, the rest comes with the progress
+    pub fn begin_construction<'f>(&'f mut self) {
+        self.status = BuildingStatus::UnderConstruction { progress: 0.0 };
+        self.hp = (self.max_hp / 10).max(1);
+        self.build_timer.reset();
+    }
+
+    //Takes off and frees its cells, only for finished buildings with nothing in production
+    pub fn lift_off<'f>(&'f mut self, game_map: &'f mut World) {
+        self.release_footprint(game_map);
+        self.status = BuildingStatus::Flying;
+        self.collider_type = Collidable::AirCollidable;
+        self.position = (self.collider.x as f32, self.collider.y as f32);
+        self.move_target = None;
+        self.button_panel_index = 0;
+    }
+
+    //Flies so its centre ends up on the point, giving up on landing
+    pub fn fly_to<'f>(&'f mut self, point: Point) {
+        if !self.is_airborne() {
+            return;
+        }
+
+        self.status = BuildingStatus::Flying;
+        self.move_target = Some(Point::new(point.x - self.collider.w / 2,
+            point.y - self.collider.h / 2));
+    }
+
+    //Flies over the site picked with the ghost and touches down once it's there
+    pub fn land_at<'f>(&'f mut self, site: Point) {
+        if !self.is_airborne() {
+            return;
+        }
+
+        self.status = BuildingStatus::Landing;
+        self.move_target = Some(site);
+    }
+
+    //Hovers where it is
+    pub fn stop<'f>(&'f mut self) {
+        if self.is_airborne() {
+            self.status = BuildingStatus::Flying;
+            self.move_target = None;
         }
+    }
+
+    //Steps towards the move target, called once per frame. Returns true while a
+    //landing building is over its site
+    pub fn update_flight<'f>(&'f mut self) -> bool {
+        let target = match self.move_target {
+            Some(target) if self.is_airborne() => target,
+            _ => { return self.status == BuildingStatus::Landing; }
+        };
+
+        let dx = target.x as f32 - self.position.0;
+        let dy = target.y as f32 - self.position.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let speed = self.building_type.get_speed();
 
+        if distance <= speed {
+            self.set_position((target.x as f32, target.y as f32));
+            self.move_target = None;
+            return self.status == BuildingStatus::Landing;
+        }
+        self.set_position((self.position.0 + dx / distance * speed,
+            self.position.1 + dy / distance * speed));
         false
     }
-    
-    pub fn get_constructed<'f>(&'f self, tx_mgr: &'f TextureManager) -> Building {
-        return self.constructing.to_owned().unwrap().build_building(tx_mgr);
+
+    //Settles on the cells under it, the caller makes sure they're free
+    pub fn land<'f>(&'f mut self, game_map: &'f mut World) {
+        self.status = BuildingStatus::Built;
+        self.collider_type = Collidable::GroundCollidable;
+        self.move_target = None;
+        self.button_panel_index = 0;
+        game_map.set_occupied(self.collider, true);
     }
 
-    fn set_button_panel<'f>(&'f mut self, index: usize) {
+    fn set_position<'f>(&'f mut self, position: (f32, f32)) {
+        self.position = position;
+        self.collider.x = position.0.round() as i32;
+        self.collider.y = position.1.round() as i32;
+        self.sprite.set_location(self.collider.top_left());
+    }
+
+    //Advances construction while a worker is on site and pauses it otherwise,
+    //returns true once the building is finished
+    pub fn update_build<'f>(&'f mut self, worker_on_site: bool) -> bool {
+        let progress = match self.status {
+            BuildingStatus::UnderConstruction { progress } => progress,
+            _ => { return false; }
+        };
+
+        if !worker_on_site {
+            self.build_timer.stop();
+            return false;
+        }
+        if !self.build_timer.is_running() {
+            self.build_timer.start();
+        }
+
+        let new_progress = (self.build_timer.elapsed().as_secs_f32() /
+            self.building_type.get_build_time().as_secs_f32()).min(1.0);
+        //Damage taken while building isn't healed, only the new progress adds hp
+        let grown = |progress: f32| (progress * (self.max_hp - self.max_hp / 10) as f32) as i32;
+        self.hp = (self.hp + grown(new_progress) - grown(progress)).min(self.max_hp);
+
+        if new_progress >= 1.0 {
+            self.build_timer.stop();
+            self.status = BuildingStatus::Built;
+            self.button_panel_index = 0; // Was a panel of the construction card
+            return true;
+        }
+        self.status = BuildingStatus::UnderConstruction { progress: new_progress };
+        false
+    }
+
+    //Buildings don't move, they only fire at whatever comes in range
+    pub fn update_attack<'f>(&'f mut self, targets: &'f [Target]) -> Option<Shot> {
+        if self.status != BuildingStatus::Built {
+            return None;
+        }
+
+        let weapon = self.weapon.as_mut()?;
+        let target = combat::nearest_in_range(weapon, self.collider, self.team, targets)?;
+        let damage = weapon.fire()?;
+        Some(Shot::new(weapon, damage, self.id, self.team, self.collider.center(), target))
+    }
+
+    //Buildings can't walk into range, a pending cast out of range fails
+    pub fn update_cast<'f>(&'f mut self, targets: &'f [Target])
+            -> Option<Result<Cast, CastError>> {
+        let cast = self.caster.pending.take()?;
+        let target_area = match cast.target {
+            CastTarget::None => Some(self.collider),
+            CastTarget::Entity(id) => targets.iter()
+                .find(|target| target.id == id)
+                .map(|target| target.collider),
+            CastTarget::Point(point) => Some(Rect::from_center(point, 1, 1)),
+        };
+
+        match target_area {
+            Some(area) if ability::in_range(cast.ability, self.collider, area) => {},
+            _ => { return Some(Err(CastError::InvalidTarget)); }
+        }
+
+        Some(self.caster.spend(cast.ability).map(|_| cast))
+    }
+
+    pub fn set_button_panel<'f>(&'f mut self, index: usize) {
         self.button_panel_index = index;
     }
    
+    //Centres the ghost on the mouse, snapped to the grid and kept inside the map
     pub fn move_building<'f>(&'f mut self, mouse_point: Point, cam_viewport: Rect, 
-            grid: &'f mut Vec<Vec<Cell>>) {
-        let mut world_pos: Point = Point::new(
-            cam_viewport.x + mouse_point.x - self.collider.w + 25,
-            cam_viewport.y + mouse_point.y - self.collider.h + 25 
-        );
-
-        world_pos.x = min(world_pos.x,
-            (grid.len() - 2) as i32 * 25 - self.width_in_cells() * 25);
-        world_pos.y = min(world_pos.y,
-            grid[0].len() as i32 * 25 - self.height_in_cells() * 25);
-
-        world_pos.x = max(world_pos.x, 0);
-        world_pos.y = max(world_pos.y, 0);
+            grid: &'f [Vec<Cell>]) {
+        let max_x = (grid.len() as i32 * 25 - self.collider.w).max(0);
+        let max_y = (grid[0].len() as i32 * 25 - self.collider.h).max(0);
+        //Rounds to the nearest cell corner so the ghost doesn't lag behind the mouse
+        let snap = |pixel: i32, size: i32| (pixel - size / 2 + 12).div_euclid(25) * 25;
 
-        world_pos.x = world_pos.x - (world_pos.x % 25);
-        world_pos.y = world_pos.y - (world_pos.y % 25);
+        let world_pos = Point::new(
+            snap(cam_viewport.x + mouse_point.x, self.collider.w).clamp(0, max_x),
+            snap(cam_viewport.y + mouse_point.y, self.collider.h).clamp(0, max_y));
 
         self.sprite.set_location(world_pos);
         self.collider.x = world_pos.x;
         self.collider.y = world_pos.y;
     }
+
+    //Buildings that go on a geyser jump onto the one under the ghost's centre
+    pub fn snap_to_geyser<'f>(&'f mut self, game_map: &'f World) {
+        if !self.building_type.requires_geyser() {
+            return;
+        }
+
+        let centre = self.collider.center();
+        if let Some(geyser) = game_map.resource_fields.iter()
+                .find(|field| field.kind == ResourceKind::Gas &&
+                    field.collider.contains_point(centre)) {
+            self.sprite.set_location(geyser.collider.top_left());
+            self.collider.x = geyser.collider.x;
+            self.collider.y = geyser.collider.y;
+        }
+    }
     
-    pub fn highlight_cells<'f>(&'f self, game_map: &'f mut World) {
+    //Tints the cells under the ghost, blockers are the ground units that are in the way
+    pub fn highlight_cells<'f>(&'f self, game_map: &'f mut World, blockers: &'f [Rect]) {
         let cell_x = self.x_in_cells();
         let cell_y = self.y_in_cells();
         let w_cells = self.width_in_cells();
         let h_cells = self.height_in_cells();
+        //A refinery either sits on a free geyser or doesn't fit at all
+        let on_geyser = game_map.free_geyser_at(self.collider).is_some();
 
         let mut i: i32 = 0;
         while i < w_cells {
@@ -204,7 +422,12 @@ impl Building {
             while j < h_cells {
                 let ii = (cell_x + i) as usize;
                 let jj = (cell_y + j) as usize;
-                game_map.grid[ii][jj].highlight();
+                let valid = if self.building_type.requires_geyser() {
+                    on_geyser
+                } else {
+                    game_map.check_cell(ii, jj, blockers).is_ok()
+                };
+                game_map.grid[ii][jj].highlight(valid);
                 j += 1;
             }
             i += 1;
@@ -230,11 +453,19 @@ impl Building {
         }
     }
 
-    fn start_construction<'f>(&'f mut self, building_type: BuildingType,
-        bottom_right_ui: Vec<UiElement>) {
+    fn start_construction<'f>(&'f mut self, product: ConstructionType,
+        bottom_right_ui: Vec<UiElement>) -> bool {
         
-        self.constructing = Some(Construction::new(building_type, self.faction,
-            self.team, building_type.get_build_time(), bottom_right_ui));
+        //Nothing is trained while airborne or before the building is finished
+        if self.status != BuildingStatus::Built ||
+                self.production_queue.len() >= general::MAX_QUEUE_LENGTH {
+            return false;
+        }
+
+        self.production_queue.push_back(Construction::new(product, self.faction,
+            self.team, product.get_build_time(), bottom_right_ui));
+        self.start_next_construction();
+        true
     }
 }
 
@@ -244,12 +475,17 @@ impl Renderable for Building {
             BuildingStatus::Built => {
                 self.sprite.render(tx_mgr, canvas);
             },
-            BuildingStatus::Placing => {
+            BuildingStatus::UnderConstruction { .. } => {
+                self.sprite.render(tx_mgr, canvas);
+            },
+            BuildingStatus::Flying | BuildingStatus::Landing => {
+                self.sprite.render(tx_mgr, canvas);
+            },
+            BuildingStatus::Placing | BuildingStatus::NotBuilt => {
                 let mut temp_rect = self.sprite.texture_rect;
                 temp_rect.x += 128;
                 self.sprite.render_with_custom(tx_mgr, canvas, None, Some(temp_rect));
             },
-            BuildingStatus::NotBuilt => {}
         }
     }
 
@@ -264,13 +500,56 @@ impl Selectable for Building {
     }
 
     fn get_buttons<'f>(&'f self) -> &[Option<Button>; 16] {
-        &self.buttons[self.button_panel_index]
+        let first_panel = match self.status {
+            BuildingStatus::Built => { 0 },
+            BuildingStatus::Flying | BuildingStatus::Landing => { self.flying_panel },
+            _ => { self.button_panel_limit }
+        };
+        &self.buttons[first_panel + self.button_panel_index]
+    }
+}
+
+//Rally onto an entity follows it while it's alive, then stays where it was last seen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rally {
+    Point(Point),
+    Entity { id: u32, last_seen: Point },
+}
+
+impl Rally {
+    pub fn get_point<'f>(&'f self) -> Point {
+        match self {
+            Rally::Point(point) => { *point },
+            Rally::Entity { last_seen, .. } => { *last_seen },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConstructionType {
+    Unit(UnitType),
+}
+
+impl ConstructionType {
+    pub fn get_build_time<'f>(&'f self) -> Duration {
+        match self {
+            ConstructionType::Unit(unit_type) => { unit_type.get_build_time() },
+        }
+    }
+
+    //Button that queues this, its icon is shown in the production queue
+    pub fn get_button_function<'f>(&'f self) -> ButtonFunction {
+        match self {
+            ConstructionType::Unit(UnitType::Worker) => { ButtonFunction::MakeWorker },
+            ConstructionType::Unit(UnitType::Marine) => { ButtonFunction::MakeMarine },
+            ConstructionType::Unit(UnitType::Wraith) => { ButtonFunction::MakeWraith },
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct Construction {
-    pub building_type: BuildingType,
+    pub product: ConstructionType,
     pub faction: Faction,
     pub team: i32,
     pub bottom_right_ui: Vec<UiElement>,
@@ -279,21 +558,22 @@ pub struct Construction {
 }
 
 impl Construction {
-    pub fn new<'f>(building_type: BuildingType, faction: Faction, team: i32,
+    //The timer starts once the construction reaches the front of a queue
+    pub fn new<'f>(product: ConstructionType, faction: Faction, team: i32,
             timer_end: Duration, bottom_right_ui: Vec<UiElement>) -> Construction {
         
-        let mut new_construction = Construction {
-            building_type,
+        Construction {
+            product,
             faction,
             team,
             bottom_right_ui,
             timer: Stopwatch::new(),
             timer_end,
-        };
-        
-        new_construction.timer.start();
+        }
+    }
 
-        return new_construction;
+    pub fn progress<'f>(&'f self) -> f32 {
+        (self.timer.elapsed().as_secs_f32() / self.timer_end.as_secs_f32()).min(1.0)
     }
 
     pub fn check_timer<'f>(&'f self) -> bool {
@@ -303,52 +583,113 @@ impl Construction {
         return false;
     }
 
-    pub fn build_building<'f>(&'f self, tx_mgr: &'f TextureManager) -> Building {
-        Building::new(Point::new(0, 0), self.building_type, self.faction, self.team,
+    pub fn train_unit<'f>(&'f self, unit_type: UnitType, location: Point,
+            tx_mgr: &'f TextureManager) -> Unit {
+        Unit::new(location, unit_type, self.faction, self.team,
             self.bottom_right_ui.to_owned(), tx_mgr)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum BuildingStatus {
     Built,
-    Placing,
-    NotBuilt,
+    Placing, // Ghost following the mouse
+    NotBuilt, // Placed and paid for, waiting for its worker to get there
+    UnderConstruction { progress: f32 },
+    Flying, // Lifted off, its cells are free
+    Landing, // Flying to the site picked with its ghost
+}
+
+//Why a building's ghost can't be placed where it is, most serious first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlacementError {
+    OutOfBounds,
+    Unbuildable, // Terrain, objects or resource fields
+    OverlapsBuilding,
+    BlockedByUnit,
+    NeedsGeyser,
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementError::OutOfBounds => { write!(f, "Can't build outside the map") },
+            PlacementError::Unbuildable => { write!(f, "Can't build on that terrain") },
+            PlacementError::OverlapsBuilding => { write!(f, "Can't build on another building") },
+            PlacementError::BlockedByUnit => { write!(f, "Something is in the way") },
+            PlacementError::NeedsGeyser => { write!(f, "Must be placed on a geyser") },
+        }
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, EnumIter, Display)]
+impl std::error::Error for PlacementError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, EnumIter, Display, EnumString)]
 pub enum BuildingType {
     CommandCentre,
     Barracks,
+    Refinery,
+    SupplyDepot,
 }
 
-#[allow(unreachable_patterns)]
 impl BuildingType {
     pub fn get_all_variants() -> Vec<BuildingType> {
         BuildingType::iter().collect()
     }
 
     pub fn get_w<'f>(&'f self) -> u32 {
-        match self {
-            BuildingType::CommandCentre => { 150 },
-            BuildingType::Barracks => { 100 },
-            _ => { unimplemented!() }
-        }
+        stats::building(*self).w
     }
 
     pub fn get_h<'f>(&'f self) -> u32 {
-        match self {
-            BuildingType::CommandCentre => { 150 },
-            BuildingType::Barracks => { 100 },
-            _ => { unimplemented!() }
-        }
+        stats::building(*self).h
+    }
+
+    //Supply added to the owner's cap once built
+    pub fn get_supply_provided<'f>(&'f self) -> i32 {
+        stats::building(*self).supply
+    }
+
+    //Placed exactly on top of a geyser instead of on free cells
+    pub fn requires_geyser<'f>(&'f self) -> bool {
+        matches!(self, BuildingType::Refinery)
+    }
+
+    //Workers carry their loads back to these
+    pub fn is_resource_depot<'f>(&'f self) -> bool {
+        matches!(self, BuildingType::CommandCentre)
+    }
+
+    //Pixels per frame while flying
+    pub fn get_speed<'f>(&'f self) -> f32 {
+        stats::building(*self).speed
+    }
+
+    pub fn can_lift<'f>(&'f self) -> bool {
+        self.get_speed() > 0.0
+    }
+
+    pub fn get_max_hp<'f>(&'f self) -> i32 {
+        stats::building(*self).max_hp
+    }
+
+    pub fn get_max_energy<'f>(&'f self) -> f32 {
+        stats::building(*self).max_energy
+    }
+
+    pub fn get_armor<'f>(&'f self) -> i32 {
+        stats::building(*self).armor
+    }
+
+    pub fn get_weapon<'f>(&'f self) -> Option<Weapon> {
+        stats::building(*self).weapon
+    }
+
+    pub fn get_cost<'f>(&'f self) -> Cost {
+        stats::building(*self).cost
     }
 
     pub fn get_build_time<'f>(&'f self) -> Duration {
-        match self {
-            BuildingType::CommandCentre => { Duration::from_millis(general::CCBUILD_TIME) },
-            BuildingType::Barracks => { Duration::from_millis(general::BARRACKS_BUILD_TIME) },
-            _ => { unimplemented!() }
-        }
+        stats::building(*self).build_time
     }
 }
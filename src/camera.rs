@@ -1,9 +1,12 @@
 use sdl2::keyboard::Keycode;
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 use std::cmp::{max, min};
 
 use super::World;
 
+//Where the world is drawn inside the render buffer
+pub const WORLD_OFFSET: i32 = 45;
+
 //Represents bindings for player camera
 pub struct Camera {
 	pub move_up: bool,
@@ -95,6 +98,11 @@ impl Camera {
         }
     }
 
+    pub fn screen_to_world<'f>(&'f self, point: Point) -> Point {
+        Point::new(self.viewport.x + point.x - WORLD_OFFSET,
+            self.viewport.y + point.y - WORLD_OFFSET)
+    }
+
     pub fn move_cam<'f>(&'f mut self, game_map: &'f World) {
         if self.can_move {
             let mut x_move: i32 = 0;
@@ -120,7 +128,7 @@ impl Camera {
             self.viewport.set_y(max(self.viewport.y, 40));
 
             self.viewport.set_x(min(self.viewport.x,
-                (game_map.world_sprites.len() as i32 - 1) * 
+                game_map.world_sprites.len() as i32 * 
                     game_map.world_sprites[0][0].loc_rect.w as i32 - 
                         self.viewport.width() as i32 + 55));
             
@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use sdl2::keyboard::Keycode;
+
+use crate::ability::AbilityKind;
+use crate::building::BuildingType;
+use crate::datafile::{self, DataError, Entity, Global, expect_args, expect_args_between};
+use crate::ui::ButtonFunction;
+use crate::unit::UnitType;
+
+//Command card file format (plain text, '#' starts a comment):
+//
+//  version 1
+//  unit <unit type>             (the panels below belong to this unit until the next entry)
+//  building <building type>
+//  construction                 (shown by any building that isn't finished yet)
+//  flying                       (shown by any building that has lifted off)
+//  panel <name>                 (the first panel of an entry is shown on selection)
+//  button <slot> <action> [key <hotkey>] [opens <panel>]
+//
+//Slots go from 0 to 15, left to right and top to bottom. An action is a ButtonFunction
+//or an AbilityKind, hotkeys use SDL key names. A button that opens a panel of the same
+//entry switches the card to it after running its action.
+//Every unit and building type needs an entry, one without panels gets an empty card.
+//Stats have to be loaded first, only buildings with a speed can use LiftOff.
+pub const CARDS_FORMAT_VERSION: u32 = 1;
+
+pub const SLOT_COUNT: usize = 16;
+
+//Handled by main and the camera before any command card sees them
+const RESERVED_KEYS: [Keycode; 6] =
+    [Keycode::Escape, Keycode::F1, Keycode::Up, Keycode::Down, Keycode::Left, Keycode::Right];
+
+static CARDS: Global<CardsFile> = Global::new("Command cards");
+
+//One button of a panel, opens is the index of a panel in the same card
+#[derive(Clone, Copy)]
+pub struct ButtonDef {
+    pub slot: usize,
+    pub function: ButtonFunction,
+    pub hotkey: Option<Keycode>,
+    pub opens: Option<usize>,
+}
+
+pub type Panel = Vec<ButtonDef>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Unit(UnitType),
+    Building(BuildingType),
+    Construction,
+    Flying,
+}
+
+impl From<Entity> for Entry {
+    fn from(entity: Entity) -> Entry {
+        match entity {
+            Entity::Unit(unit_type) => Entry::Unit(unit_type),
+            Entity::Building(building_type) => Entry::Building(building_type),
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt<'f>(&'f self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Unit(unit_type) => { write!(f, "unit {}", unit_type) },
+            Entry::Building(building_type) => { write!(f, "building {}", building_type) },
+            Entry::Construction => { write!(f, "construction") },
+            Entry::Flying => { write!(f, "flying") },
+        }
+    }
+}
+
+impl Entry {
+    //Keeps actions on the entities the player code runs them for
+    fn allows<'f>(&'f self, function: ButtonFunction) -> bool {
+        match function {
+            ButtonFunction::OpenPanel | ButtonFunction::Back => { true },
+            ButtonFunction::MakeWorker | ButtonFunction::MakeMarine |
+                    ButtonFunction::MakeWraith => {
+                matches!(self, Entry::Building(..))
+            },
+            ButtonFunction::PlaceBarracks | ButtonFunction::PlaceCommandCentre |
+                    ButtonFunction::PlaceRefinery | ButtonFunction::PlaceSupplyDepot => {
+                matches!(self, Entry::Unit(unit_type) if unit_type.can_build())
+            },
+            ButtonFunction::Stop => { matches!(self, Entry::Unit(..) | Entry::Flying) },
+            ButtonFunction::CancelConstruction => { *self == Entry::Construction },
+            ButtonFunction::LiftOff => {
+                matches!(self, Entry::Building(building_type) if building_type.can_lift())
+            },
+            ButtonFunction::Land => { *self == Entry::Flying },
+            ButtonFunction::Ability(..) => { *self != Entry::Construction },
+        }
+    }
+}
+
+//Entry being read, with its panel names and the panel name each button opens
+struct PendingCard {
+    entry: Entry,
+    names: Vec<String>,
+    panels: Vec<Panel>,
+    opens: Vec<(usize, usize, usize, String)>, // Line, panel, button, panel name
+}
+
+//Command cards of every unit and building type, loaded once at startup
+pub struct CardsFile {
+    pub units: HashMap<UnitType, Vec<Panel>>,
+    pub buildings: HashMap<BuildingType, Vec<Panel>>,
+    pub construction: Vec<Panel>,
+    pub flying: Vec<Panel>,
+    has_construction: bool,
+    has_flying: bool,
+}
+
+impl CardsFile {
+    pub fn load<'f>(path: &'f Path) -> Result<CardsFile, DataError> {
+        CardsFile::parse(&datafile::read(path)?)
+    }
+
+    pub fn parse<'f>(contents: &'f str) -> Result<CardsFile, DataError> {
+        let mut lines = datafile::lines(contents);
+        datafile::check_version(&mut lines, CARDS_FORMAT_VERSION)?;
+
+        let mut cards = CardsFile {
+            units: HashMap::new(),
+            buildings: HashMap::new(),
+            construction: vec![],
+            flying: vec![],
+            has_construction: false,
+            has_flying: false,
+        };
+        let mut current: Option<PendingCard> = None;
+
+        for (line_nr, line) in lines {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[0] {
+                "unit" | "building" | "construction" | "flying" => {
+                    let entry = match parts[0] {
+                        "construction" | "flying" => {
+                            expect_args(line_nr, &parts, 0)?;
+                            if parts[0] == "flying" { Entry::Flying } else { Entry::Construction }
+                        },
+                        _ => {
+                            expect_args(line_nr, &parts, 1)?;
+                            datafile::parse_entity(line_nr, parts[0], parts[1])?.into()
+                        }
+                    };
+
+                    if let Some(done) = current.take() {
+                        cards.insert(done)?;
+                    }
+                    if cards.has(entry) {
+                        return Err(DataError::Parse {
+                            line: line_nr,
+                            message: format!("{} is listed twice", entry),
+                        });
+                    }
+                    current = Some(PendingCard {
+                        entry,
+                        names: vec![],
+                        panels: vec![],
+                        opens: vec![],
+                    });
+                },
+                "panel" => {
+                    expect_args(line_nr, &parts, 1)?;
+                    let card = pending(line_nr, &mut current, "panel")?;
+                    if card.names.iter().any(|name| name == parts[1]) {
+                        return Err(DataError::Parse {
+                            line: line_nr,
+                            message: format!("{} has two panels called '{}'", card.entry,
+                                parts[1]),
+                        });
+                    }
+                    card.names.push(parts[1].to_string());
+                    card.panels.push(vec![]);
+                },
+                "button" => {
+                    expect_args_between(line_nr, &parts, 2, 6)?;
+                    let card = pending(line_nr, &mut current, "button")?;
+                    parse_button(line_nr, &parts, card)?;
+                },
+                other => {
+                    return Err(DataError::Parse {
+                        line: line_nr,
+                        message: format!("unknown line '{}'", other),
+                    });
+                }
+            }
+        }
+
+        if let Some(done) = current.take() {
+            cards.insert(done)?;
+        }
+
+        let entries = Entity::get_all_variants().into_iter().map(Entry::from)
+            .chain([Entry::Construction, Entry::Flying]);
+        for entry in entries {
+            if !cards.has(entry) {
+                return Err(DataError::MissingEntity(entry.to_string()));
+            }
+        }
+
+        Ok(cards)
+    }
+
+    fn has<'f>(&'f self, entry: Entry) -> bool {
+        match entry {
+            Entry::Unit(unit_type) => { self.units.contains_key(&unit_type) },
+            Entry::Building(building_type) => { self.buildings.contains_key(&building_type) },
+            Entry::Construction => { self.has_construction },
+            Entry::Flying => { self.has_flying },
+        }
+    }
+
+    //Resolves the panels buttons open, they can come later in the entry than the button
+    fn insert<'f>(&'f mut self, mut card: PendingCard) -> Result<(), DataError> {
+        for (line, panel, button, name) in card.opens.iter() {
+            match card.names.iter().position(|known| known == name) {
+                Some(index) => { card.panels[*panel][*button].opens = Some(index); },
+                None => {
+                    return Err(DataError::Parse {
+                        line: *line,
+                        message: format!("{} has no panel called '{}'", card.entry, name),
+                    });
+                }
+            }
+        }
+
+        if card.panels.is_empty() {
+            card.panels.push(vec![]);
+        }
+
+        match card.entry {
+            Entry::Unit(unit_type) => { self.units.insert(unit_type, card.panels); },
+            Entry::Building(building_type) => {
+                self.buildings.insert(building_type, card.panels);
+            },
+            Entry::Construction => {
+                self.construction = card.panels;
+                self.has_construction = true;
+            },
+            Entry::Flying => {
+                self.flying = card.panels;
+                self.has_flying = true;
+            },
+        }
+        Ok(())
+    }
+}
+
+//Makes the command cards available to new units and buildings, only the first call counts
+pub fn init(cards: CardsFile) {
+    CARDS.init(cards);
+}
+
+pub fn unit(unit_type: UnitType) -> &'static [Panel] {
+    &CARDS.get().units[&unit_type]
+}
+
+pub fn building(building_type: BuildingType) -> &'static [Panel] {
+    &CARDS.get().buildings[&building_type]
+}
+
+pub fn construction() -> &'static [Panel] {
+    &CARDS.get().construction
+}
+
+pub fn flying() -> &'static [Panel] {
+    &CARDS.get().flying
+}
+
+fn pending<'f>(line: usize, current: &'f mut Option<PendingCard>, kind: &'f str)
+        -> Result<&'f mut PendingCard, DataError> {
+    match current.as_mut() {
+        Some(card) if kind == "panel" || !card.panels.is_empty() => Ok(card),
+        Some(_) => Err(DataError::Parse {
+            line,
+            message: "'button' comes before any panel".to_string(),
+        }),
+        None => Err(DataError::Parse {
+            line,
+            message: format!("'{}' comes before any unit or building", kind),
+        }),
+    }
+}
+
+fn parse_button<'f>(line: usize, parts: &'f [&'f str], card: &'f mut PendingCard)
+        -> Result<(), DataError> {
+    let slot: usize = parts[1].parse().ok()
+        .filter(|slot| *slot < SLOT_COUNT)
+        .ok_or(DataError::Parse {
+            line,
+            message: format!("slot must be a number from 0 to {}, not '{}'", SLOT_COUNT - 1,
+                parts[1]),
+        })?;
+
+    let function = ButtonFunction::from_str(parts[2]).ok()
+        .or(AbilityKind::from_str(parts[2]).ok().map(ButtonFunction::Ability))
+        .ok_or(DataError::Parse {
+            line,
+            message: format!("unknown action '{}'", parts[2]),
+        })?;
+    if !card.entry.allows(function) {
+        return Err(DataError::Parse {
+            line,
+            message: format!("{} can't use '{}'", card.entry, parts[2]),
+        });
+    }
+
+    let mut hotkey: Option<Keycode> = None;
+    let mut opens: Option<&str> = None;
+    let mut i: usize = 3;
+    while i < parts.len() {
+        let value = match parts.get(i + 1) {
+            Some(value) => *value,
+            None => {
+                return Err(DataError::Parse {
+                    line,
+                    message: format!("'{}' needs a value", parts[i]),
+                });
+            }
+        };
+
+        match parts[i] {
+            "key" if hotkey.is_none() => {
+                hotkey = Some(parse_hotkey(line, value)?);
+            },
+            "opens" if opens.is_none() => {
+                opens = Some(value);
+            },
+            "key" | "opens" => {
+                return Err(DataError::Parse {
+                    line,
+                    message: format!("'{}' is set twice", parts[i]),
+                });
+            },
+            other => {
+                return Err(DataError::Parse {
+                    line,
+                    message: format!("unknown button option '{}'", other),
+                });
+            }
+        }
+        i += 2;
+    }
+
+    let panel_index = card.panels.len() - 1;
+    let panel = &mut card.panels[panel_index];
+    if panel.iter().any(|button| button.slot == slot) {
+        return Err(DataError::Parse {
+            line,
+            message: format!("slot {} is used twice in panel '{}'", slot,
+                card.names[panel_index]),
+        });
+    }
+    if let Some(keycode) = hotkey.filter(|key| panel.iter().any(|b| b.hotkey == Some(*key))) {
+        return Err(DataError::Parse {
+            line,
+            message: format!("key {} is used twice in panel '{}'", keycode.name(),
+                card.names[panel_index]),
+        });
+    }
+
+    if let Some(name) = opens {
+        card.opens.push((line, panel_index, panel.len(), name.to_string()));
+    }
+    panel.push(ButtonDef { slot, function, hotkey, opens: None });
+    Ok(())
+}
+
+fn parse_hotkey<'f>(line: usize, name: &'f str) -> Result<Keycode, DataError> {
+    match Keycode::from_name(name) {
+        Some(keycode) if RESERVED_KEYS.contains(&keycode) => Err(DataError::Parse {
+            line,
+            message: format!("key '{}' is reserved", name),
+        }),
+        Some(keycode) => Ok(keycode),
+        None => Err(DataError::Parse {
+            line,
+            message: format!("unknown key '{}'", name),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafile::testing::{self, swapped, line_of};
+    use crate::stats::{self, StatsFile};
+
+    const SHIPPED: &str = include_str!("../assets/data/command_cards.cards");
+
+    //LiftOff checks the building's speed, so the shipped stats go in first
+    fn parse<'f>(contents: &'f str) -> Result<CardsFile, DataError> {
+        let stats = include_str!("../assets/data/entities.stats");
+        stats::init(StatsFile::parse(stats).unwrap());
+        CardsFile::parse(contents)
+    }
+
+    //Parses the shipped cards with from swapped for to, expecting an error on that line
+    fn assert_swap_fails<'f>(from: &'f str, to: &'f str, expected: &'f str) {
+        let contents = swapped(SHIPPED, from, to);
+        let line = line_of(&contents, to.lines().last().unwrap());
+        let err = parse(&contents).err().expect("cards parsed without an error");
+
+        testing::assert_parse_error(&err, line, expected);
+    }
+
+    #[test]
+    fn shipped_cards_parse() {
+        let cards = parse(SHIPPED).unwrap();
+
+        assert_eq!(cards.units.len(), UnitType::get_all_variants().len());
+        assert_eq!(cards.buildings.len(), BuildingType::get_all_variants().len());
+        assert_eq!(cards.construction.len(), 1);
+        assert_eq!(cards.flying.len(), 1);
+
+        let worker = &cards.units[&UnitType::Worker];
+        assert_eq!(worker.len(), 2);
+        let build = worker[0].iter().find(|button| button.slot == 12).unwrap();
+        assert_eq!(build.opens, Some(1));
+        assert_eq!(build.hotkey, Keycode::from_name("B"));
+        let back = worker[1].iter().find(|button| button.slot == 15).unwrap();
+        assert_eq!(back.opens, Some(0));
+
+        assert!(cards.buildings[&BuildingType::Refinery][0].is_empty());
+    }
+
+    #[test]
+    fn duplicate_slot() {
+        assert_swap_fails("button 2 PlaceRefinery", "button 1 PlaceRefinery",
+            "slot 1 is used twice in panel 'build'");
+    }
+
+    #[test]
+    fn slot_out_of_range() {
+        assert_swap_fails("button 2 PlaceRefinery", "button 16 PlaceRefinery",
+            "slot must be a number from 0 to 15, not '16'");
+    }
+
+    #[test]
+    fn duplicate_hotkey() {
+        assert_swap_fails("PlaceRefinery key R", "PlaceRefinery key B",
+            "is used twice in panel 'build'");
+    }
+
+    #[test]
+    fn reserved_hotkey() {
+        assert_swap_fails("CancelConstruction key X", "CancelConstruction key Escape",
+            "key 'Escape' is reserved");
+    }
+
+    #[test]
+    fn opens_unknown_panel() {
+        assert_swap_fails("opens build", "opens tier2",
+            "unit Worker has no panel called 'tier2'");
+    }
+
+    #[test]
+    fn action_the_entity_cannot_use() {
+        assert_swap_fails("building Refinery",
+            "building Refinery\n    panel main\n        button 0 LiftOff",
+            "building Refinery can't use 'LiftOff'");
+        assert_swap_fails("button 1 Stop key S", "button 1 MakeWorker key S",
+            "flying can't use 'MakeWorker'");
+    }
+
+    #[test]
+    fn button_before_panel() {
+        assert_swap_fails("building Refinery", "building Refinery\n    button 0 Stop",
+            "'button' comes before any panel");
+    }
+
+    #[test]
+    fn missing_entry() {
+        let contents = swapped(SHIPPED,
+            "flying\n    panel main\n        button 1 Stop key S\n        button 12 Land key L\n",
+            "");
+
+        match parse(&contents).err() {
+            Some(DataError::MissingEntity(entity)) => { assert_eq!(entity, "flying"); },
+            _ => { panic!("expected a missing entry"); }
+        }
+    }
+}
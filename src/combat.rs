@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use stopwatch::Stopwatch;
+use sdl2::rect::{Point, Rect};
+use strum_macros::EnumString;
+
+use crate::general::Collidable;
+use crate::player::Player;
+use crate::projectile::{Projectile, ProjectileStats};
+use crate::world::World;
+
+//Weapon stats together with the cooldown timer of whoever carries it
+#[derive(Clone, Copy)]
+pub struct Weapon {
+    pub range: f32, // Pixels between the attacker's and the target's colliders
+    pub damage: i32,
+    pub cooldown: Duration,
+    pub targets: WeaponTargets,
+    pub projectile: Option<ProjectileStats>, // None hits instantly
+    timer: Stopwatch,
+}
+
+impl Weapon {
+    pub fn new(range: f32, damage: i32, cooldown: u64, targets: WeaponTargets) -> Weapon {
+        Weapon {
+            range,
+            damage,
+            cooldown: Duration::from_millis(cooldown),
+            targets,
+            projectile: None,
+            timer: Stopwatch::new(),
+        }
+    }
+
+    pub fn with_projectile(mut self, stats: ProjectileStats) -> Weapon {
+        self.projectile = Some(stats);
+        self
+    }
+
+    pub fn can_target<'f>(&'f self, collider_type: Collidable) -> bool {
+        self.targets.allows(collider_type)
+    }
+
+    pub fn in_range<'f>(&'f self, attacker: Rect, target: Rect) -> bool {
+        edge_distance(attacker, target) <= self.range
+    }
+
+    pub fn ready<'f>(&'f self) -> bool {
+        !self.timer.is_running() || self.timer.elapsed() >= self.cooldown
+    }
+
+    //Returns the damage dealt, None while cooling down
+    pub fn fire<'f>(&'f mut self) -> Option<i32> {
+        if !self.ready() {
+            return None;
+        }
+        self.timer.restart();
+        Some(self.damage)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, EnumString)]
+pub enum WeaponTargets {
+    Ground,
+    Air,
+    Both,
+}
+
+impl WeaponTargets {
+    pub fn allows<'f>(&'f self, collider_type: Collidable) -> bool {
+        matches!((self, collider_type),
+            (_, Collidable::AbsoluteCollidable) |
+            (WeaponTargets::Ground | WeaponTargets::Both,
+                Collidable::GroundCollidable | Collidable::GroundUncollidable) |
+            (WeaponTargets::Air | WeaponTargets::Both,
+                Collidable::AirCollidable | Collidable::AirUncollidable))
+    }
+}
+
+//Snapshot of something that can be attacked, taken once per frame
+#[derive(Clone, Copy)]
+pub struct Target {
+    pub id: u32,
+    pub team: i32,
+    pub collider: Rect,
+    pub collider_type: Collidable,
+}
+
+//A weapon going off, becomes a Hit right away or a Projectile
+#[derive(Clone, Copy)]
+pub struct Shot {
+    pub shooter: u32,
+    pub team: i32,
+    pub origin: Point,
+    pub target: Target,
+    pub damage: i32,
+    pub targets: WeaponTargets,
+    pub projectile: Option<ProjectileStats>,
+}
+
+impl Shot {
+    pub fn new<'f>(weapon: &'f Weapon, damage: i32, shooter: u32, team: i32, origin: Point,
+            target: Target) -> Shot {
+        Shot {
+            shooter,
+            team,
+            origin,
+            target,
+            damage,
+            targets: weapon.targets,
+            projectile: weapon.projectile,
+        }
+    }
+}
+
+//Damage dealt to the entity with this id
+#[derive(Clone, Copy)]
+pub struct Hit {
+    pub id: u32,
+    pub damage: i32,
+}
+
+//Every hit does at least 1 damage, no matter the armor
+pub fn damage_after_armor(damage: i32, armor: i32) -> i32 {
+    (damage - armor).max(1)
+}
+
+pub fn collect_targets<'f>(players: &'f [Player]) -> Vec<Target> {
+    let mut targets: Vec<Target> = vec![];
+
+    for player in players.iter() {
+        for unit in player.units.iter() {
+            targets.push(Target {
+                id: unit.id,
+                team: unit.team,
+                collider: unit.collider,
+                collider_type: unit.collider_type,
+            });
+        }
+        for building in player.buildings.iter()
+                .filter(|building| building.on_grid() || building.is_airborne()) {
+            targets.push(Target {
+                id: building.id,
+                team: building.team,
+                collider: building.collider,
+                collider_type: building.collider_type,
+            });
+        }
+    }
+
+    targets
+}
+
+//Closest target of another team the weapon can hit from where the attacker stands
+pub fn nearest_in_range<'f>(weapon: &'f Weapon, attacker: Rect, team: i32,
+        targets: &'f [Target]) -> Option<Target> {
+    targets.iter()
+        .filter(|target| target.team != team && weapon.can_target(target.collider_type) &&
+            weapon.in_range(attacker, target.collider))
+        .min_by(|a, b| edge_distance(attacker, a.collider)
+            .total_cmp(&edge_distance(attacker, b.collider)))
+        .copied()
+}
+
+//Id of the entity under point, from any team
+pub fn entity_at<'f>(players: &'f [Player], point: Point) -> Option<u32> {
+    collect_targets(players).iter()
+        .find(|target| target.collider.contains_point(point))
+        .map(|target| target.id)
+}
+
+//Id of the entity under point that doesn't belong to team
+pub fn enemy_at<'f>(players: &'f [Player], point: Point, team: i32) -> Option<u32> {
+    collect_targets(players).iter()
+        .find(|target| target.team != team && target.collider.contains_point(point))
+        .map(|target| target.id)
+}
+
+//Lets every armed unit and building pick a target and fire, moves projectiles,
+//then applies the damage and removes whatever died
+pub fn update<'f>(players: &'f mut [Player], projectiles: &'f mut Vec<Projectile>,
+        game_map: &'f mut World) {
+    let targets = collect_targets(players);
+    let mut shots: Vec<Shot> = vec![];
+    let mut hits: Vec<Hit> = vec![];
+
+    players.iter_mut().for_each(|player| {
+        player.update_casts(&targets, game_map, projectiles);
+        player.update_attacks(&targets, game_map, &mut shots);
+    });
+
+    for shot in shots.iter() {
+        match shot.projectile {
+            Some(stats) => {
+                projectiles.push(Projectile::new(shot, stats));
+            },
+            None => { hits.push(Hit { id: shot.target.id, damage: shot.damage }); }
+        }
+    }
+
+    projectiles.iter_mut().for_each(|projectile| projectile.update(&targets, &mut hits));
+    projectiles.retain(|projectile| !projectile.done);
+
+    players.iter_mut().for_each(|player| {
+        player.take_hits(&hits);
+        player.remove_dead(game_map);
+    });
+}
+
+//Gap between two rectangles, 0 when they touch or overlap
+pub fn edge_distance(a: Rect, b: Rect) -> f32 {
+    let dx = (b.left() - a.right()).max(a.left() - b.right()).max(0) as f32;
+    let dy = (b.top() - a.bottom()).max(a.top() - b.bottom()).max(0) as f32;
+    (dx * dx + dy * dy).sqrt()
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::building::BuildingType;
+use crate::unit::UnitType;
+
+//Pieces shared by the plain text data files (maps, entity stats and command cards).
+//Every file starts with a 'version' line, '#' starts a comment and values are
+//separated by whitespace. Line numbers in errors count from 1.
+
+#[derive(Debug)]
+pub enum DataError {
+    Io { path: PathBuf, source: std::io::Error },
+    MissingVersion,
+    UnsupportedVersion { found: u32, expected: u32 },
+    Parse { line: usize, message: String },
+    MissingEntity(String),
+}
+
+impl fmt::Display for DataError {
+    fn fmt<'f>(&'f self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io { path, source } => {
+                write!(f, "{}: {}", path.display(), source)
+            },
+            DataError::MissingVersion => {
+                write!(f, "file must start with a 'version' line")
+            },
+            DataError::UnsupportedVersion { found, expected } => {
+                write!(f, "unsupported version {} (expected {})", found, expected)
+            },
+            DataError::Parse { line, message } => {
+                write!(f, "line {}: {}", line, message)
+            },
+            DataError::MissingEntity(entity) => {
+                write!(f, "file has no entry for {}", entity)
+            },
+        }
+    }
+}
+
+impl std::error::Error for DataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DataError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+//A unit or building type named by a 'unit <type>' or 'building <type>' line
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Entity {
+    Unit(UnitType),
+    Building(BuildingType),
+}
+
+impl fmt::Display for Entity {
+    fn fmt<'f>(&'f self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entity::Unit(unit_type) => { write!(f, "unit {}", unit_type) },
+            Entity::Building(building_type) => { write!(f, "building {}", building_type) },
+        }
+    }
+}
+
+impl Entity {
+    pub fn get_all_variants() -> Vec<Entity> {
+        UnitType::get_all_variants().into_iter().map(Entity::Unit)
+            .chain(BuildingType::get_all_variants().into_iter().map(Entity::Building))
+            .collect()
+    }
+}
+
+//Holds a data file loaded once at startup, readable from anywhere after that
+pub struct Global<T> {
+    name: &'static str,
+    cell: OnceLock<T>,
+}
+
+impl<T> Global<T> {
+    pub const fn new(name: &'static str) -> Global<T> {
+        Global { name, cell: OnceLock::new() }
+    }
+
+    //Only the first call counts
+    pub fn init<'f>(&'f self, value: T) {
+        let _ = self.cell.set(value);
+    }
+
+    pub fn get<'f>(&'f self) -> &'f T {
+        match self.cell.get() {
+            Some(value) => value,
+            None => { panic!("{} used before they were loaded", self.name); }
+        }
+    }
+}
+
+pub fn read<'f>(path: &'f Path) -> Result<String, DataError> {
+    fs::read_to_string(path).map_err(|source| DataError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+//Numbered lines with comments and surrounding whitespace stripped, blank ones skipped
+pub fn lines<'f>(contents: &'f str) -> impl Iterator<Item = (usize, &'f str)> {
+    contents.lines().enumerate()
+        .map(|(i, line)| (i + 1, strip_comment(line).trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+//Takes the first line, which has to be 'version <expected>'
+pub fn check_version<'f, I: Iterator<Item = (usize, &'f str)>>(lines: &mut I, expected: u32)
+        -> Result<(), DataError> {
+    let (line_nr, line) = match lines.next() {
+        Some(first) => first,
+        None => { return Err(DataError::MissingVersion); }
+    };
+
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts[0] != "version" {
+        return Err(DataError::MissingVersion);
+    }
+    expect_args(line_nr, &parts, 1)?;
+    let found: u32 = parse_num(line_nr, parts[1])?;
+    if found != expected {
+        return Err(DataError::UnsupportedVersion { found, expected });
+    }
+
+    Ok(())
+}
+
+pub fn parse_entity<'f>(line: usize, kind: &'f str, name: &'f str)
+        -> Result<Entity, DataError> {
+    let entity = match kind {
+        "unit" => UnitType::from_str(name).ok().map(Entity::Unit),
+        _ => BuildingType::from_str(name).ok().map(Entity::Building),
+    };
+
+    entity.ok_or(DataError::Parse {
+        line,
+        message: format!("unknown {} type '{}'", kind, name),
+    })
+}
+
+pub fn expect_args<'f>(line: usize, parts: &'f [&'f str], count: usize)
+        -> Result<(), DataError> {
+    expect_args_between(line, parts, count, count)
+}
+
+pub fn expect_args_between<'f>(line: usize, parts: &'f [&'f str], min: usize, max: usize)
+        -> Result<(), DataError> {
+    let found = parts.len() - 1;
+    if found < min || found > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(DataError::Parse {
+            line,
+            message: format!("'{}' takes {} values, found {}", parts[0], expected, found),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn parse_num<'f, T: FromStr>(line: usize, value: &'f str) -> Result<T, DataError> {
+    value.parse::<T>().map_err(|_| DataError::Parse {
+        line,
+        message: format!("'{}' is not a valid number", value),
+    })
+}
+
+pub fn at_least<'f, T: FromStr + PartialOrd + fmt::Display>(line: usize, value: &'f str,
+        min: T) -> Result<T, DataError> {
+    let parsed: T = parse_num(line, value)?;
+    if parsed < min {
+        return Err(DataError::Parse {
+            line,
+            message: format!("'{}' can't be below {}", value, min),
+        });
+    }
+
+    Ok(parsed)
+}
+
+pub fn parse_bool<'f>(line: usize, value: &'f str) -> Result<bool, DataError> {
+    value.parse::<bool>().map_err(|_| DataError::Parse {
+        line,
+        message: format!("'{}' must be true or false", value),
+    })
+}
+
+fn strip_comment<'f>(line: &'f str) -> &'f str {
+    match line.find('#') {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+//Helpers for the parser tests of each data file
+#[cfg(test)]
+pub mod testing {
+    use super::DataError;
+
+    //contents with the first from swapped for to
+    pub fn swapped<'f>(contents: &'f str, from: &'f str, to: &'f str) -> String {
+        assert!(contents.contains(from), "'{}' isn't in the file", from);
+        contents.replacen(from, to, 1)
+    }
+
+    //Line number of the first line containing text
+    pub fn line_of<'f>(contents: &'f str, text: &'f str) -> usize {
+        contents.lines().position(|line| line.contains(text)).unwrap() + 1
+    }
+
+    pub fn assert_parse_error<'f>(err: &'f DataError, expected_line: usize,
+            expected: &'f str) {
+        match err {
+            DataError::Parse { line, message } => {
+                assert_eq!(*line, expected_line);
+                assert!(message.contains(expected), "unexpected message '{}'", message);
+            },
+            err => { panic!("expected a parse error, got '{}'", err); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_skip_comments_and_blanks() {
+        let contents = "# header\nversion 1\n\n  size 2 3  # trailing\n   \n#only\nend";
+        let lines: Vec<(usize, &str)> = lines(contents).collect();
+
+        assert_eq!(lines, vec![(2, "version 1"), (4, "size 2 3"), (7, "end")]);
+    }
+
+    #[test]
+    fn version_line_is_taken() {
+        let mut lines = lines("# comment\nversion 3\nnext");
+
+        assert!(check_version(&mut lines, 3).is_ok());
+        assert_eq!(lines.next(), Some((3, "next")));
+    }
+
+    #[test]
+    fn missing_version() {
+        assert!(matches!(check_version(&mut lines(""), 1), Err(DataError::MissingVersion)));
+        assert!(matches!(check_version(&mut lines("size 1 1\nversion 1"), 1),
+            Err(DataError::MissingVersion)));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        assert!(matches!(check_version(&mut lines("version 2"), 1),
+            Err(DataError::UnsupportedVersion { found: 2, expected: 1 })));
+
+        let err = check_version(&mut lines("\nversion two"), 1).err().unwrap();
+        testing::assert_parse_error(&err, 2, "'two' is not a valid number");
+        let err = check_version(&mut lines("version"), 1).err().unwrap();
+        testing::assert_parse_error(&err, 1, "'version' takes 1 values, found 0");
+    }
+
+    #[test]
+    fn argument_counts() {
+        let parts = ["button", "0", "Stop", "key"];
+
+        assert!(expect_args(4, &parts, 3).is_ok());
+        testing::assert_parse_error(&expect_args(4, &parts, 2).err().unwrap(), 4,
+            "'button' takes 2 values, found 3");
+        assert!(expect_args_between(4, &parts, 2, 6).is_ok());
+        testing::assert_parse_error(&expect_args_between(4, &parts, 4, 6).err().unwrap(), 4,
+            "'button' takes 4 to 6 values, found 3");
+    }
+
+    #[test]
+    fn numbers_and_bools() {
+        assert_eq!(parse_num::<i32>(1, "-5").unwrap(), -5);
+        testing::assert_parse_error(&parse_num::<u32>(6, "-5").err().unwrap(), 6,
+            "'-5' is not a valid number");
+
+        assert_eq!(at_least(1, "2.5", 0.0).unwrap(), 2.5);
+        testing::assert_parse_error(&at_least(2, "0", 1).err().unwrap(), 2,
+            "'0' can't be below 1");
+
+        assert!(parse_bool(1, "true").unwrap());
+        testing::assert_parse_error(&parse_bool(3, "yes").err().unwrap(), 3,
+            "'yes' must be true or false");
+    }
+
+    #[test]
+    fn entities() {
+        assert!(parse_entity(1, "unit", "Worker").unwrap() == Entity::Unit(UnitType::Worker));
+        assert!(parse_entity(1, "building", "Barracks").unwrap() ==
+            Entity::Building(BuildingType::Barracks));
+        assert_eq!(Entity::Building(BuildingType::Barracks).to_string(), "building Barracks");
+
+        testing::assert_parse_error(&parse_entity(9, "unit", "Zergling").err().unwrap(), 9,
+            "unknown unit type 'Zergling'");
+        testing::assert_parse_error(&parse_entity(9, "building", "Worker").err().unwrap(), 9,
+            "unknown building type 'Worker'");
+    }
+}
@@ -0,0 +1,254 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+
+use std::path::PathBuf;
+
+use crate::camera::Camera;
+use crate::general::Collidable;
+use crate::map::{TILE_SIZE, GRASS_TILE, DIRT_TILE};
+use crate::player::Player;
+use crate::resources::{ResourceField, ResourceKind};
+use crate::sprite::{TextureManager, TextureType};
+use crate::ui::Notification;
+use crate::world::{World, WorldObject};
+
+const START_SIZE: u32 = 150; // Command Centre footprint
+const OBJECT_TYPES: [Collidable; 5] = [
+    Collidable::GroundCollidable,
+    Collidable::GroundUncollidable,
+    Collidable::AirCollidable,
+    Collidable::AirUncollidable,
+    Collidable::AbsoluteCollidable,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EditorBrush {
+    Tile(i32),
+    Object,
+    StartLocation,
+    Resource(ResourceKind),
+}
+
+//Map editor, toggled with F1
+//  1/2: paint grass/dirt, 3: place objects (C cycles collidable type),
+//  4: place start locations, 5/6: place mineral/gas fields, right click deletes, S saves
+pub struct Editor {
+    pub active: bool,
+    pub brush: EditorBrush,
+    pub object_type: usize,
+    pub save_path: PathBuf,
+    painting: bool,
+    hover: Option<Point>,
+    notification: Option<Notification>,
+}
+
+impl Editor {
+    pub fn new(save_path: PathBuf) -> Editor {
+        Editor {
+            active: false,
+            brush: EditorBrush::Tile(GRASS_TILE),
+            object_type: 0,
+            save_path,
+            painting: false,
+            hover: None,
+            notification: None,
+        }
+    }
+
+    pub fn toggle<'f>(&'f mut self) {
+        self.active = !self.active;
+        self.painting = false;
+        self.hover = None;
+    }
+
+    //Returns true if the event was used by the editor
+    pub fn handle_event<'f>(&'f mut self, event: &'f Event, game_map: &'f mut World,
+            players: &'f [Player], cam: &'f Camera, atlas: &'f TextureManager) -> bool {
+        match event {
+            Event::KeyDown { keycode: Some(keycode), .. } => {
+                match keycode {
+                    Keycode::Num1 => { self.brush = EditorBrush::Tile(GRASS_TILE); },
+                    Keycode::Num2 => { self.brush = EditorBrush::Tile(DIRT_TILE); },
+                    Keycode::Num3 => { self.brush = EditorBrush::Object; },
+                    Keycode::Num4 => { self.brush = EditorBrush::StartLocation; },
+                    Keycode::Num5 => {
+                        self.brush = EditorBrush::Resource(ResourceKind::Minerals);
+                    },
+                    Keycode::Num6 => { self.brush = EditorBrush::Resource(ResourceKind::Gas); },
+                    Keycode::C => {
+                        self.object_type = (self.object_type + 1) % OBJECT_TYPES.len();
+                        self.notify(format!("Object type: {}", OBJECT_TYPES[self.object_type]));
+                    },
+                    Keycode::S => { self.save(game_map); },
+                    _ => { return false; }
+                }
+                true
+            },
+            Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                let world_point = cam.screen_to_world(Point::new(*x, *y));
+                match mouse_btn {
+                    MouseButton::Left => {
+                        self.painting = true;
+                        self.apply(world_point, game_map, atlas);
+                    },
+                    MouseButton::Right => {
+                        self.erase(world_point, game_map, players);
+                    },
+                    _ => {}
+                }
+                true
+            },
+            Event::MouseButtonUp { .. } => {
+                self.painting = false;
+                true
+            },
+            Event::MouseMotion { x, y, .. } => {
+                let world_point = cam.screen_to_world(Point::new(*x, *y));
+                self.hover = Some(world_point);
+                if self.painting {
+                    if let EditorBrush::Tile(..) = self.brush {
+                        self.apply(world_point, game_map, atlas);
+                    }
+                }
+                false // Still used for camera panning
+            },
+            _ => { false }
+        }
+    }
+
+    fn apply<'f>(&'f mut self, world_point: Point, game_map: &'f mut World,
+            atlas: &'f TextureManager) {
+        let tile = snap(world_point, TILE_SIZE);
+        let map_area = Rect::new(0, 0,
+            game_map.world_encode.len() as u32 * TILE_SIZE as u32,
+            game_map.world_encode[0].len() as u32 * TILE_SIZE as u32);
+        if !map_area.contains_point(world_point) {
+            return;
+        }
+
+        match self.brush {
+            EditorBrush::Tile(tile_index) => {
+                game_map.set_tile(world_point, tile_index, atlas);
+            },
+            EditorBrush::Object => {
+                let collider = Rect::new(tile.x, tile.y, TILE_SIZE as u32, TILE_SIZE as u32);
+                if !game_map.objects.iter().any(|object| object.collider == collider) {
+                    game_map.add_object(WorldObject::new(TextureType::Filler, collider,
+                        OBJECT_TYPES[self.object_type], atlas));
+                }
+            },
+            EditorBrush::StartLocation => {
+                let start = snap(world_point, 25);
+                let area = Rect::new(start.x, start.y, START_SIZE, START_SIZE);
+                if !map_area.contains_rect(area) {
+                    return;
+                }
+
+                if game_map.start_locations.iter().any(|other|
+                        Rect::new(other.x, other.y, START_SIZE, START_SIZE)
+                            .has_intersection(area)) {
+                    self.notify(String::from("Start locations can't overlap"));
+                } else if game_map.objects.iter().any(|object| object.blocks_ground() &&
+                        object.collider.has_intersection(area)) {
+                    self.notify(String::from("Start location is blocked by an object"));
+                } else {
+                    game_map.start_locations.push(start);
+                }
+            },
+            EditorBrush::Resource(kind) => {
+                let area = Rect::new(tile.x, tile.y, kind.get_w(), kind.get_h());
+                if map_area.contains_rect(area) && !game_map.resource_fields.iter()
+                        .any(|field| field.collider.has_intersection(area)) {
+                    game_map.add_resource_field(ResourceField::new(kind, tile,
+                        kind.get_default_amount(), atlas));
+                }
+            },
+        }
+    }
+
+    fn erase<'f>(&'f mut self, world_point: Point, game_map: &'f mut World,
+            players: &'f [Player]) {
+        match self.brush {
+            EditorBrush::StartLocation => {
+                game_map.start_locations.retain(|start|
+                    !Rect::new(start.x, start.y, START_SIZE, START_SIZE)
+                        .contains_point(world_point));
+            },
+            EditorBrush::Resource(..) => {
+                game_map.remove_resource_field_at(world_point);
+            },
+            _ => {
+                let buildings: Vec<Rect> = players.iter()
+                    .flat_map(|player| player.buildings.iter())
+                    .filter(|building| building.on_grid())
+                    .map(|building| building.collider)
+                    .collect();
+                game_map.remove_object_at(world_point, &buildings);
+            }
+        }
+    }
+
+    pub fn save<'f>(&'f mut self, game_map: &'f World) {
+        if game_map.start_locations.is_empty() {
+            self.notify(String::from("Can't save a map without start locations"));
+            return;
+        }
+
+        let message = match game_map.to_map().save(&self.save_path) {
+            Ok(()) => { format!("Saved map to {}", self.save_path.display()) },
+            Err(err) => { format!("Failed to save map: {}", err) }
+        };
+        self.notify(message);
+    }
+
+    fn notify<'f>(&'f mut self, message: String) {
+        self.notification = Some(Notification::new(message));
+    }
+
+    //Outlines start locations and the hovered tile, drawn in world space
+    pub fn render<'f>(&'f self, game_map: &'f World, canvas: &'f mut WindowCanvas) {
+        let previous_color = canvas.draw_color();
+
+        canvas.set_draw_color(Color::RGBA(0, 120, 255, 255));
+        for start in game_map.start_locations.iter() {
+            let _ = canvas.draw_rect(Rect::new(start.x, start.y, START_SIZE, START_SIZE));
+        }
+
+        if let Some(hover) = self.hover {
+            let hover_rect = match self.brush {
+                EditorBrush::StartLocation => {
+                    let start = snap(hover, 25);
+                    Rect::new(start.x, start.y, START_SIZE, START_SIZE)
+                },
+                EditorBrush::Resource(kind) => {
+                    let tile = snap(hover, TILE_SIZE);
+                    Rect::new(tile.x, tile.y, kind.get_w(), kind.get_h())
+                },
+                _ => {
+                    let tile = snap(hover, TILE_SIZE);
+                    Rect::new(tile.x, tile.y, TILE_SIZE as u32, TILE_SIZE as u32)
+                }
+            };
+            canvas.set_draw_color(Color::RGBA(255, 255, 255, 255));
+            let _ = canvas.draw_rect(hover_rect);
+        }
+
+        canvas.set_draw_color(previous_color);
+    }
+
+    //Drawn in screen space, over the player's UI
+    pub fn render_ui<'f>(&'f self, tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
+        if let Some(notification) = self.notification.as_ref()
+                .filter(|notification| !notification.expired()) {
+            notification.render(tx_mgr, canvas);
+        }
+    }
+}
+
+fn snap(point: Point, size: i32) -> Point {
+    Point::new(point.x - point.x.rem_euclid(size), point.y - point.y.rem_euclid(size))
+}
@@ -0,0 +1,260 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::pathfinding::{self, PathGrid, CellRegion, STRAIGHT_COST, DIAGONAL_COST};
+
+//Group moves at or above this size share one flow field instead of running A* per unit
+pub const FLOW_FIELD_GROUP_SIZE: usize = 8;
+//Cells of slack around the group and goal that the field is allowed to route through,
+//units that can't reach the goal inside it fall back to their own A* path
+const REGION_MARGIN: usize = 20;
+const UNREACHABLE: u32 = u32::MAX;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1),
+    (1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+
+//Integration field towards one goal cell, each cell points at its cheapest neighbour.
+//Only the region around the move order is flooded, so occupancy changes elsewhere
+//never force a rebuild.
+#[derive(Clone)]
+pub struct FlowField {
+    pub goal: (usize, usize),
+    pub footprint: usize,
+    pub region: CellRegion,
+    pub dirty: bool,
+    integration: Vec<u32>,
+    directions: Vec<Option<(i32, i32)>>,
+}
+
+impl FlowField {
+    //Starts are the cells the units in the move order stand on, a blocked goal
+    //is moved to the closest free cell
+    pub fn new<'f, G: PathGrid>(grid: &'f G, goal: (usize, usize), footprint: usize,
+            starts: &'f [(usize, usize)]) -> FlowField {
+        let goal = pathfinding::nearest_free_cell(grid, goal, footprint.max(1)).unwrap_or(goal);
+        let mut region = CellRegion::new(goal.0, goal.1, 1, 1);
+        starts.iter().for_each(|start| region = region.union(CellRegion::new(start.0, start.1,
+            footprint, footprint)));
+        region = region.expand(REGION_MARGIN, grid.width(), grid.height());
+
+        let mut field = FlowField {
+            goal,
+            footprint: footprint.max(1),
+            region,
+            dirty: false,
+            integration: vec![],
+            directions: vec![],
+        };
+        field.rebuild(grid);
+        field
+    }
+
+    pub fn mark_dirty_if_affected<'f>(&'f mut self, changed: &'f [CellRegion]) {
+        if changed.iter().any(|area| area.intersects(&self.region)) {
+            self.dirty = true;
+        }
+    }
+
+    pub fn rebuild<'f, G: PathGrid>(&'f mut self, grid: &'f G) {
+        let size = self.region.w * self.region.h;
+        self.integration = vec![UNREACHABLE; size];
+        self.directions = vec![None; size];
+        self.dirty = false;
+
+        //Dijkstra outwards from the goal
+        let mut open: BinaryHeap<Reverse<(u32, usize, usize)>> = BinaryHeap::new();
+        if self.passable(grid, self.goal.0 as i32, self.goal.1 as i32) {
+            let goal_index = self.index(self.goal);
+            self.integration[goal_index] = 0;
+            open.push(Reverse((0, self.goal.0, self.goal.1)));
+        }
+
+        while let Some(Reverse((cost, x, y))) = open.pop() {
+            if cost > self.integration[self.index((x, y))] {
+                continue;
+            }
+
+            for (dx, dy) in NEIGHBOURS {
+                if !self.can_step(grid, (x, y), (dx, dy)) {
+                    continue;
+                }
+
+                let next = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                let next_cost = cost +
+                    if dx != 0 && dy != 0 { DIAGONAL_COST } else { STRAIGHT_COST };
+                let next_index = self.index(next);
+                if next_cost < self.integration[next_index] {
+                    self.integration[next_index] = next_cost;
+                    open.push(Reverse((next_cost, next.0, next.1)));
+                }
+            }
+        }
+
+        //Point every reachable cell at its cheapest neighbour
+        let mut x = self.region.x;
+        while x < self.region.x + self.region.w {
+            let mut y = self.region.y;
+            while y < self.region.y + self.region.h {
+                let mut best = self.integration[self.index((x, y))];
+                let mut best_direction = None;
+
+                for (dx, dy) in NEIGHBOURS {
+                    if !self.can_step(grid, (x, y), (dx, dy)) {
+                        continue;
+                    }
+                    let next = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                    if self.integration[self.index(next)] < best {
+                        best = self.integration[self.index(next)];
+                        best_direction = Some((dx, dy));
+                    }
+                }
+
+                let index = self.index((x, y));
+                self.directions[index] = best_direction;
+                y += 1;
+            }
+            x += 1;
+        }
+    }
+
+    //Step towards the goal from cell, None at the goal or where the goal can't be reached
+    pub fn direction_at<'f>(&'f self, cell: (usize, usize)) -> Option<(i32, i32)> {
+        if !self.region.contains(cell) {
+            return None;
+        }
+        self.directions[self.index(cell)]
+    }
+
+    fn index<'f>(&'f self, cell: (usize, usize)) -> usize {
+        (cell.0 - self.region.x) * self.region.h + (cell.1 - self.region.y)
+    }
+
+    fn passable<'f, G: PathGrid>(&'f self, grid: &'f G, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && self.region.contains((x as usize, y as usize)) &&
+            grid.fits(x, y, self.footprint)
+    }
+
+    //Same corner cutting rule as A*
+    fn can_step<'f, G: PathGrid>(&'f self, grid: &'f G, from: (usize, usize),
+            direction: (i32, i32)) -> bool {
+        let (x, y) = (from.0 as i32, from.1 as i32);
+        let (dx, dy) = direction;
+
+        if !self.passable(grid, x + dx, y + dy) {
+            return false;
+        }
+
+        if dx != 0 && dy != 0 {
+            return self.passable(grid, x + dx, y) && self.passable(grid, x, y + dy);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Open width x height grid with the given cells blocked
+    fn grid(width: usize, height: usize, blocked: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        let mut grid = vec![vec![false; height]; width];
+        for (x, y) in blocked {
+            grid[*x][*y] = true;
+        }
+        grid
+    }
+
+    //Every cell of column x blocked except the ones in gaps
+    fn wall(x: usize, height: usize, gaps: &[usize]) -> Vec<(usize, usize)> {
+        (0..height).filter(|y| !gaps.contains(y)).map(|y| (x, y)).collect()
+    }
+
+    //Cells visited by following the field from start, stops when there is no direction
+    fn follow<'f>(field: &'f FlowField, start: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut cells = vec![start];
+        let mut cell = start;
+        while let Some((dx, dy)) = field.direction_at(cell) {
+            cell = ((cell.0 as i32 + dx) as usize, (cell.1 as i32 + dy) as usize);
+            cells.push(cell);
+            assert!(cells.len() < 1000, "field loops");
+        }
+        cells
+    }
+
+    #[test]
+    fn points_straight_at_the_goal() {
+        let grid = grid(10, 5, &[]);
+        let field = FlowField::new(&grid, (5, 2), 1, &[(0, 2)]);
+
+        assert_eq!(field.direction_at((0, 2)), Some((1, 0)));
+        assert_eq!(field.direction_at((5, 2)), None);
+        assert_eq!(follow(&field, (0, 2)).last(), Some(&(5, 2)));
+    }
+
+    #[test]
+    fn routes_around_a_wall() {
+        let grid = grid(7, 7, &wall(3, 7, &[6]));
+        let field = FlowField::new(&grid, (6, 0), 1, &[(0, 0)]);
+        let cells = follow(&field, (0, 0));
+
+        assert_eq!(cells.last(), Some(&(6, 0)));
+        assert!(cells.contains(&(3, 6)));
+        assert!(cells.iter().all(|(x, y)| !grid[*x][*y]));
+    }
+
+    #[test]
+    fn blocked_goal_moves_to_a_free_cell() {
+        let grid = grid(5, 5, &[(2, 2)]);
+        let field = FlowField::new(&grid, (2, 2), 1, &[(0, 0)]);
+
+        assert_ne!(field.goal, (2, 2));
+        assert!(!grid[field.goal.0][field.goal.1]);
+        assert_eq!(follow(&field, (0, 0)).last(), Some(&field.goal));
+    }
+
+    #[test]
+    fn large_footprint_avoids_a_narrow_gap() {
+        let grid = grid(7, 7, &wall(3, 7, &[0, 5, 6]));
+        let field = FlowField::new(&grid, (5, 0), 2, &[(0, 0)]);
+        let cells = follow(&field, (0, 0));
+
+        assert_eq!(cells.last(), Some(&(5, 0)));
+        assert!(cells.iter().all(|(x, y)| grid.fits(*x as i32, *y as i32, 2)));
+        assert!(cells.contains(&(3, 5)));
+    }
+
+    #[test]
+    fn cut_off_cells_have_no_direction() {
+        let grid = grid(7, 7, &wall(3, 7, &[]));
+        let field = FlowField::new(&grid, (6, 0), 1, &[(0, 0)]);
+
+        assert_eq!(field.direction_at((0, 0)), None);
+        assert!(field.direction_at((5, 5)).is_some());
+    }
+
+    #[test]
+    fn cells_outside_the_region_have_no_direction() {
+        let grid = grid(REGION_MARGIN * 3, 5, &[]);
+        let field = FlowField::new(&grid, (0, 2), 1, &[(1, 2)]);
+        let outside = (REGION_MARGIN * 2 + 2, 2);
+
+        assert!(!field.region.contains(outside));
+        assert_eq!(field.direction_at(outside), None);
+        assert_eq!(field.direction_at((REGION_MARGIN, 2)), Some((-1, 0)));
+    }
+
+    #[test]
+    fn only_changes_inside_the_region_dirty_the_field() {
+        let grid = grid(REGION_MARGIN * 3, 5, &[]);
+        let mut field = FlowField::new(&grid, (0, 2), 1, &[(1, 2)]);
+
+        field.mark_dirty_if_affected(&[CellRegion::new(REGION_MARGIN * 2 + 2, 0, 2, 2)]);
+        assert!(!field.dirty);
+
+        field.mark_dirty_if_affected(&[CellRegion::new(4, 0, 2, 2)]);
+        assert!(field.dirty);
+    }
+}
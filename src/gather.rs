@@ -0,0 +1,153 @@
+use sdl2::rect::Rect;
+
+use crate::combat;
+use crate::player::Player;
+use crate::resources::{ResourceField, ResourceKind};
+use crate::unit::Unit;
+use crate::world::World;
+
+pub const MINING_TICKS: u32 = 100; // Frames spent mining one load
+pub const GATHER_RANGE: f32 = 10.0; // Pixels between the worker and the field or depot
+const SWAP_RANGE: f32 = 400.0; // How far a worker looks for a free field when its own is taken
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GatherState {
+    ToField,
+    Mining(u32), // Frames left
+    Returning,
+}
+
+//A worker's trip between a resource field and the nearest depot
+#[derive(Clone, Copy)]
+pub struct Gather {
+    pub field: u32,
+    pub kind: ResourceKind,
+    pub state: GatherState,
+    pub carrying: i32,
+}
+
+impl Gather {
+    pub fn new<'f>(field: &'f ResourceField) -> Gather {
+        Gather {
+            field: field.id,
+            kind: field.kind,
+            state: GatherState::ToField,
+            carrying: 0,
+        }
+    }
+}
+
+//Moves every gathering worker one step along its trip, deposits go to the worker's owner
+pub fn update<'f>(players: &'f mut [Player], game_map: &'f mut World) {
+    //Only one worker mines a field at a time, claims are rebuilt every frame so
+    //dead or reassigned workers free their field
+    game_map.resource_fields.iter_mut().for_each(|field| field.harvester = None);
+    for unit in players.iter().flat_map(|player| player.units.iter()) {
+        if let Some(Gather { field, state: GatherState::Mining(..), .. }) = unit.gather {
+            if let Some(field) = game_map.resource_field_mut(field) {
+                field.harvester = Some(unit.id);
+            }
+        }
+    }
+
+    players.iter_mut().for_each(|player| player.update_gathering(game_map));
+}
+
+//Advances one worker, returns what it dropped off at a depot this frame
+//extractors holds the ids of the owner's refineries, gas is only mined through them
+pub fn step<'f>(unit: &'f mut Unit, depots: &'f [Rect], extractors: &'f [u32],
+        game_map: &'f mut World) -> Option<(ResourceKind, i32)> {
+    let mut gather = unit.gather?;
+    let mut deposit: Option<(ResourceKind, i32)> = None;
+
+    match gather.state {
+        GatherState::ToField => {
+            let current = game_map.resource_field(gather.field).copied();
+            if gather.kind == ResourceKind::Gas {
+                //Gas only flows through the owner's refinery, workers wait for their geyser
+                let usable = match current {
+                    Some(field) => match field.extractor {
+                        Some(id) => extractors.contains(&id) && !field.depleted(),
+                        None => false,
+                    },
+                    None => false,
+                };
+                if !usable {
+                    unit.stop();
+                    return None;
+                }
+            } else if current.is_none_or(|field| field.harvester.is_some()) {
+                //Taken or mined out patches are swapped for the closest free one nearby
+                match game_map.nearest_free_field(gather.kind, unit.get_location(), SWAP_RANGE) {
+                    Some(id) => { gather.field = id; },
+                    None if current.is_none() => {
+                        unit.stop();
+                        return None;
+                    },
+                    None => {} // Waits for its field
+                }
+            }
+
+            let field = *game_map.resource_field(gather.field)?;
+            if combat::edge_distance(unit.collider, field.collider) <= GATHER_RANGE {
+                if unit.is_moving() {
+                    unit.arrive();
+                }
+                if field.harvester.is_none() {
+                    //Other workers headed here would stop against it otherwise
+                    unit.destination = None;
+                    game_map.resource_field_mut(field.id)?.harvester = Some(unit.id);
+                    gather.state = GatherState::Mining(MINING_TICKS);
+                }
+            } else if field.harvester.is_none() &&
+                    (unit.destination != Some(field.collider.center()) || !unit.is_moving()) {
+                unit.move_to(field.collider.center(), &*game_map);
+            }
+        },
+        GatherState::Mining(ticks) => {
+            match game_map.resource_field_mut(gather.field) {
+                Some(..) if ticks > 1 => { gather.state = GatherState::Mining(ticks - 1); },
+                Some(field) => {
+                    gather.carrying = field.take(gather.kind.get_load());
+                    gather.state = GatherState::Returning;
+                    //Empty geysers stay, a refinery may be standing on them
+                    if field.depleted() && field.kind == ResourceKind::Minerals {
+                        let id = field.id;
+                        game_map.remove_resource_field(id);
+                    }
+                },
+                None => { gather.state = GatherState::ToField; }
+            }
+        },
+        GatherState::Returning => {
+            let depot = depots.iter()
+                .min_by(|a, b| combat::edge_distance(unit.collider, **a)
+                    .total_cmp(&combat::edge_distance(unit.collider, **b)))
+                .copied();
+
+            match depot {
+                Some(depot) if combat::edge_distance(unit.collider, depot) <= GATHER_RANGE => {
+                    if unit.is_moving() {
+                        unit.arrive();
+                    }
+                    deposit = Some((gather.kind, gather.carrying));
+                    gather.carrying = 0;
+                    gather.state = GatherState::ToField;
+                },
+                Some(depot) => {
+                    if unit.destination != Some(depot.center()) || !unit.is_moving() {
+                        unit.move_to(depot.center(), &*game_map);
+                    }
+                },
+                None => { // Holds on to the load until a depot is built
+                    if unit.is_moving() {
+                        unit.arrive();
+                    }
+                }
+            }
+        }
+    }
+
+    unit.gather = Some(gather);
+    deposit
+}
@@ -1,17 +1,47 @@
-use crate::ui::{ButtonFunction, Button, UiElement, UIProperties, XAlignment, YAlignment};
+use crate::ui::{Button, UiElement, UIProperties, XAlignment, YAlignment};
 use crate::sprite::{Sprite, TextureManager, TextureType};
+use crate::cards::{ButtonDef, Panel, SLOT_COUNT};
 
+use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::WindowCanvas;
-use strum_macros::Display;
+use strum_macros::{Display, EnumString};
 
 use std::cmp::max;
+use std::sync::atomic::{AtomicU32, Ordering};
 
-pub const CCBUILD_TIME: u64 = 1000;
-pub const BARRACKS_BUILD_TIME: u64 = 1000;
+pub const MAX_QUEUE_LENGTH: usize = 5;
+
+pub const CANCEL_REFUND_PERCENT: i32 = 75; // Given back for a cancelled building
+pub const STARTING_MINERALS: i32 = 50;
+pub const STARTING_GAS: i32 = 0;
+
+pub const MAX_SUPPLY: i32 = 200; // No amount of supply depots goes past this
+
+pub const MINERAL_FIELD_AMOUNT: i32 = 1500;
+pub const GAS_FIELD_AMOUNT: i32 = 2250;
+pub const MINERAL_LOAD: i32 = 5;
+pub const GAS_LOAD: i32 = 4;
+
+static NEXT_ENTITY_ID: AtomicU32 = AtomicU32::new(0);
+
+//Units and buildings are looked up by id, their indices shift when something dies
+pub fn next_entity_id() -> u32 {
+    NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+//Colour of a team's health bars
+pub fn team_color(team: i32) -> Color {
+    match team {
+        0 => { Color::RGBA(0, 200, 0, 255) },
+        1 => { Color::RGBA(220, 30, 30, 255) },
+        2 => { Color::RGBA(30, 90, 230, 255) },
+        _ => { Color::RGBA(230, 200, 30, 255) }
+    }
+}
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
 pub enum Collidable {
     GroundCollidable,
     GroundUncollidable,
@@ -22,30 +52,71 @@ pub enum Collidable {
     UI,
 }
 
+impl Collidable {
+    //Whether two entities block each other, ground and air only meet through
+    //AbsoluteCollidable, World blocks ground movement
+    pub fn collides_with<'f>(&'f self, other: Collidable) -> bool {
+        matches!((self, other),
+            (Collidable::AbsoluteCollidable, Collidable::GroundCollidable) |
+            (Collidable::AbsoluteCollidable, Collidable::AirCollidable) |
+            (Collidable::AbsoluteCollidable, Collidable::AbsoluteCollidable) |
+            (Collidable::AbsoluteCollidable, Collidable::World) |
+            (Collidable::GroundCollidable, Collidable::GroundCollidable) |
+            (Collidable::GroundCollidable, Collidable::AbsoluteCollidable) |
+            (Collidable::GroundCollidable, Collidable::World) |
+            (Collidable::AirCollidable, Collidable::AirCollidable) |
+            (Collidable::AirCollidable, Collidable::AbsoluteCollidable) |
+            (Collidable::World, Collidable::GroundCollidable) |
+            (Collidable::World, Collidable::AbsoluteCollidable))
+    }
+
+    pub fn is_air<'f>(&'f self) -> bool {
+        matches!(self, Collidable::AirCollidable | Collidable::AirUncollidable)
+    }
+
+    //How hard overlapping units push each other apart, flyers only drift
+    pub fn push_strength<'f>(&'f self) -> f32 {
+        if self.is_air() { 0.25 } else { 1.0 }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Display)]
 pub enum Faction {
     PlaceholderFaction1,
 }
 
-pub fn gen_button<'f>(atlas: &'f TextureManager, bottom_right_ui: Vec<UiElement>,
-        btn_function: ButtonFunction, order: usize) -> Option<Button> {
+pub fn gen_button<'f>(atlas: &'f TextureManager, bottom_right_ui: &'f [UiElement],
+        def: ButtonDef) -> Option<Button> {
     
-    Some(Button::new(UiElement::new(Sprite::new(
+    let mut button = Button::new(UiElement::new(Sprite::new(
         Rect::new(
-            bottom_right_ui[order + 1].collider.x,
-            bottom_right_ui[order + 1].collider.y,
+            bottom_right_ui[def.slot + 1].collider.x,
+            bottom_right_ui[def.slot + 1].collider.y,
             50, 50),
         TextureType::UI { type_index: 0 }, atlas),
         Some(UIProperties::new(
             (XAlignment::None, YAlignment::None), 
             0, 
             (0, 0, 0, 0), 
-            get_max_z(&bottom_right_ui) + 1)),
-        btn_function.get_texture_index()),
-        btn_function))
+            get_max_z(bottom_right_ui) + 1)),
+        def.function.get_texture_index()),
+        def.function);
+    button.hotkey = def.hotkey;
+    button.opens = def.opens;
+    Some(button)
 }
 
-fn get_max_z<'f>(elems: &'f Vec<UiElement>) -> usize {
+//Buttons of every panel of a command card, indexed by slot
+pub fn gen_panels<'f>(atlas: &'f TextureManager, bottom_right_ui: &'f [UiElement],
+        panels: &'f [Panel]) -> Vec<[Option<Button>; SLOT_COUNT]> {
+    panels.iter().map(|panel| {
+        let mut buttons: [Option<Button>; SLOT_COUNT] = [None; SLOT_COUNT];
+        panel.iter().for_each(|def| buttons[def.slot] = gen_button(atlas, bottom_right_ui, *def));
+        buttons
+    }).collect()
+}
+
+fn get_max_z<'f>(elems: &'f [UiElement]) -> usize {
     let mut max_z: usize = 0;
     elems.iter()
         .filter(|elem| 
@@ -74,14 +145,6 @@ pub enum Selection {
 }
 
 impl Selection {
-    pub fn is_some<'f>(&'f self) -> bool {
-        if self.to_owned() == Selection::None {
-            false
-        } else {
-            true
-        }
-    }
-    
     pub fn is_building<'f>(&'f self) -> bool {
         match self {
             Selection::Building( .. ) => {
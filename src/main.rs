@@ -9,6 +9,21 @@ mod player;
 mod ui;
 mod unit;
 mod world;
+mod map;
+mod mapgen;
+mod editor;
+mod pathfinding;
+mod flowfield;
+mod steering;
+mod combat;
+mod projectile;
+mod ability;
+mod resources;
+mod text;
+mod gather;
+mod stats;
+mod cards;
+mod datafile;
 
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
@@ -16,12 +31,14 @@ use sdl2::image::{self, InitFlag};
 use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::{Point, Rect};
 use sdl2::render::Texture;
-use sdl2::mouse::MouseButton;
+use sdl2::mouse::{MouseButton, Cursor, SystemCursor};
 
 #[allow(unused_imports)]
 use stopwatch::Stopwatch;
 
 use std::time::Duration;
+use std::env;
+use std::path::PathBuf;
 
 use sprite::*;
 use camera::*;
@@ -30,6 +47,10 @@ use general::*;
 use building::*;
 use unit::*;
 use player::*;
+use mapgen::{MapGenParams, Symmetry};
+use editor::Editor;
+use stats::StatsFile;
+use cards::CardsFile;
 
 use crate::ui::UIManager;
 
@@ -59,42 +80,57 @@ fn main() {
     
     let mut ui_mgr = UIManager::new(player_cam.viewport);
 
-    //Rendering vectors
-    #[allow(unused_mut)]
-    let mut objects: Vec<WorldObject> = vec![];
     let mut players: Vec<Player> = vec![];
 
-    //Load Sprites
-    let mut game_map = World::new(
-        {
-            let mut new_encode: Vec<Vec<i32>> = vec![vec![]];
-            {
-                let mut i: usize = 0;
-                while i < 75 {
-                    let mut j: usize = 0;
-                    new_encode.push(vec![]);
-                    while j < 75 {
-                        new_encode[i].push(1);
-                        j += 1;
-                    }
-                    i += 1;
-                }
-            }
+    //Load map ("--map <path>"), or generate one with "--seed <number>"
+    let args: Vec<String> = env::args().collect();
+    let get_arg = |name: &str| args.iter().position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1));
+    let maps_dir = env::current_dir().expect("Couldn't get current directory")
+        .join("assets/maps");
+
+    //Unit and building stats, needed before any entity is made
+    let stats_path = env::current_dir().expect("Couldn't get current directory")
+        .join("assets/data/entities.stats");
+    match StatsFile::load(&stats_path) {
+        Ok(loaded) => { stats::init(loaded); },
+        Err(err) => { panic!("Failed to load entity stats {}: {}", stats_path.display(), err) }
+    }
+
+    //Command cards, buttons are made along with each unit and building
+    let cards_path = env::current_dir().expect("Couldn't get current directory")
+        .join("assets/data/command_cards.cards");
+    match CardsFile::load(&cards_path) {
+        Ok(loaded) => { cards::init(loaded); },
+        Err(err) => {
+            panic!("Failed to load command cards {}: {}", cards_path.display(), err)
+        }
+    }
 
-            new_encode[1][1] = 2;
-            new_encode[1][2] = 2;
-            new_encode[1][3] = 2;
-            new_encode[2][1] = 2;
-            new_encode[2][2] = 2;
-            new_encode[2][3] = 2;
-            new_encode[3][1] = 2;
-            new_encode[3][2] = 2;
-            new_encode[3][3] = 2;
-
-            new_encode
+    let map_path: PathBuf;
+    let mut game_map = match get_arg("--seed") {
+        Some(seed) => {
+            map_path = maps_dir.join(format!("generated_{}.map", seed));
+            let seed: u64 = seed.parse().expect("Map seed must be a number");
+            let params = MapGenParams::new(75, 75, 2, Symmetry::Mirror);
+            match mapgen::generate(seed, &params) {
+                Ok(map) => World::from_map(&map, &tx_mgr),
+                Err(err) => { panic!("Failed to generate map: {}", err) }
+            }
         },
-        &tx_mgr
-    );
+        None => {
+            map_path = match get_arg("--map") {
+                Some(path) => PathBuf::from(path),
+                None => maps_dir.join("default.map"),
+            };
+            match World::load_from_file(&map_path, &tx_mgr) {
+                Ok(world) => world,
+                Err(err) => { panic!("Failed to load map {}: {}", map_path.display(), err) }
+            }
+        }
+    };
+
+    let mut editor = Editor::new(map_path);
       
     let mut buffer: Texture = texture_loader.create_texture_target(
         PixelFormatEnum::ARGB32, 
@@ -109,23 +145,44 @@ fn main() {
     canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
     canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
 
-    players.push(Player::new(Faction::PlaceholderFaction1, player_cam.viewport, &tx_mgr,
-        &mut ui_mgr));
-    
-    {
-        let temp = players[0].to_owned();
+    //One player per start location, players[0] is the local one
+    let mut team: usize = 0;
+    while team < game_map.start_locations.len() {
+        let mut player = Player::new(Faction::PlaceholderFaction1, player_cam.viewport,
+            &tx_mgr, &mut ui_mgr);
+        let bottom_right_ui = player.bottom_right_ui.to_owned();
+
+        player.spawn_building(Building::new(game_map.start_locations[team],
+            BuildingType::CommandCentre, Faction::PlaceholderFaction1, team as i32,
+            bottom_right_ui.to_owned(), &tx_mgr), &mut game_map);
+
+        //Starting workers, around the Command Centre
+        let mut i: i32 = 0;
+        while i < 4 {
+            let location = player.find_spawn_location(0, &game_map)
+                .expect("No room for starting workers");
+            player.units.push(Unit::new(location, UnitType::Worker,
+                Faction::PlaceholderFaction1, team as i32, bottom_right_ui.to_owned(),
+                &tx_mgr));
+            i += 1;
+        }
 
-        players[0].buildings.push(Building::new(Point::new(50, 50), BuildingType::CommandCentre,
-            Faction::PlaceholderFaction1, 0, temp.bottom_right_ui.to_owned(), &tx_mgr));
-        
-        players[0].selected = Selection::Building(0);
-        players[0].place_building(&mut game_map);
+        players.push(player);
+        team += 1;
     }
 
+    let mut projectiles: Vec<projectile::Projectile> = vec![];
+
     let mut avg: f64 = 0f64;
     let mut count: f64 = 0f64;
     
     let mut mouse_moved;
+    let mut mouse_pos = Point::new(0, 0);
+    let mut drag_start: Option<Point> = None; // Box selection, in screen space
+
+    //Crosshair while an ability waits for its target
+    let arrow_cursor = Cursor::from_system(SystemCursor::Arrow).unwrap();
+    let target_cursor = Cursor::from_system(SystemCursor::Crosshair).unwrap();
 
     let mut event_pump = sdl_context.event_pump().unwrap();
     'main: loop {
@@ -139,68 +196,136 @@ fn main() {
         mouse_moved = false;
 
         for event in event_pump.poll_iter() {
+            if editor.active && editor.handle_event(&event, &mut game_map, &players,
+                    &player_cam, &tx_mgr) {
+                continue;
+            }
+
             match event {
                 Event::Quit {..} |
                 Event::KeyDown {keycode: Some(Keycode::Escape), .. } => {
                     break 'main
                 },
+                Event::KeyDown {keycode: Some(Keycode::F1), .. } => { // Map editor
+                    players[0].dehighlight(&mut game_map);
+                    players[0].deselect();
+                    editor.toggle();
+                },
                 //Keybind handling segment
-                Event::KeyDown { keycode, .. } => { // Key pressed
-                    player_cam.check_down_key(keycode.unwrap());
+                Event::KeyDown { keycode, repeat, .. } => { // Key pressed
+                    //Command card hotkeys, held keys don't press buttons again
+                    if repeat || !players[0].check_hotkey(keycode.unwrap(), &tx_mgr,
+                            &mut game_map) {
+                        player_cam.check_down_key(keycode.unwrap());
+                    }
                 },
                 Event::KeyUp { keycode, .. } => { // Key released
                     player_cam.check_up_key(keycode.unwrap());
                 },
                 Event::MouseMotion {x, y, .. } => { // Mouse moved
+                    mouse_pos = Point::new(x, y);
+
                     if !mouse_moved {
                         let mouse_cam_point = Point::new(x, y);
 
                         player_cam.mouse_panning(x, y); // Mouse map scrolling
                         
                         if players[0].placing_building { // Move building ghost
-                            players[0].dehighlight(&mut game_map);
-                            let index = players[0].selected.index();
-                           
-                            players[0].buildings[index].move_building(mouse_cam_point,
-                                player_cam.viewport, &mut game_map.grid);
-                            
-                            players[0].buildings[index].highlight_cells(&mut game_map);
+                            let targets = combat::collect_targets(&players);
+                            players[0].move_ghost(mouse_cam_point, player_cam.viewport,
+                                &mut game_map, &targets);
                         }
 
                         mouse_moved = true;
                     }
                 }
-                Event::MouseButtonDown { /*mouse_btn, x, y,*/ .. } => {
-
+                Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                    let temp_point = Point::new(x, y);
+                    if mouse_btn == MouseButton::Left && !players[0].placing_building &&
+                            players[0].targeting.is_none() &&
+                            !players[0].bottom_right_ui[0].collider.contains_point(temp_point) {
+                        drag_start = Some(temp_point);
+                    }
                 }
                 Event::MouseButtonUp { mouse_btn, x, y, .. } => {
-                    if mouse_btn == MouseButton::Left {
+                    if mouse_btn == MouseButton::Left && !editor.active {
                         let temp_point = Point::new(x, y);
                         let mut interacted = false;
+                        let drag_rect = drag_start.take()
+                            .and_then(|start| Rect::from_enclose_points(&[start, temp_point],
+                                None))
+                            .filter(|rect| rect.width() > 8 || rect.height() > 8);
                         
                         //Check button clicks
-                        if players[0].bottom_right_ui[0].collider.contains_point(temp_point) {    
+                        if players[0].targeting.is_some() && !players[0].bottom_right_ui[0]
+                                .collider.contains_point(temp_point) { // Ability target
+                            let world_point = player_cam.screen_to_world(temp_point);
+                            let entity = combat::entity_at(&players, world_point);
+                            players[0].cast_at(world_point, entity, &game_map);
+                            interacted = true;
+                        } else if let Some(drag_rect) = drag_rect { // Box select units
+                            let top_left = player_cam.screen_to_world(drag_rect.top_left());
+                            players[0].select_units_in(Rect::new(top_left.x, top_left.y,
+                                drag_rect.width(), drag_rect.height()));
+                            interacted = true;
+                        } else if players[0].check_queue_click(temp_point) {
+                            interacted = true;
+                        } else if players[0].bottom_right_ui[0].collider
+                                .contains_point(temp_point) {    
                             let mut i: usize = 0;
                             while i < 16 {
-                                if players[0].check_button(temp_point, i) {
+                                if players[0].check_button(temp_point, i, &tx_mgr, &mut game_map) {
                                     interacted = true;
                                     break;
                                 }
                                 i += 1;
                             }
                         } else if players[0].placing_building {//Place newly constructed building
-                            players[0].place_building(&mut game_map);
+                            let targets = combat::collect_targets(&players);
+                            players[0].place_building(&mut game_map, &targets);
                             interacted = true;
                         }
 
                         if !interacted {// Select a building / unit
-                            interacted = players[0].try_selecting(temp_point);
+                            interacted = players[0]
+                                .try_selecting(player_cam.screen_to_world(temp_point));
                         }
 
                         if !interacted && !players[0].bottom_right_ui[0].collider // Deselect
                                 .contains_point(temp_point){
                             players[0].deselect();
                         }
+                    } else if mouse_btn == MouseButton::Right && !editor.active {
+                        let temp_point = Point::new(x, y);
+
+                        if players[0].targeting.is_some() { // Cancel targeting
+                            players[0].targeting = None;
+                        } else if players[0].placing_building { // Cancel placing
+                            players[0].cancel_placement(&mut game_map);
+                        } else if !players[0].bottom_right_ui[0].collider
+                                .contains_point(temp_point) {
+                            let world_point = player_cam.screen_to_world(temp_point);
+                            if players[0].selected.is_building() {
+                                //Flying buildings go there, grounded ones rally there
+                                if !players[0].command_fly(world_point) {
+                                    let entity = combat::entity_at(&players, world_point)
+                                        .or(game_map.resource_at(world_point));
+                                    players[0].set_rally(world_point, entity);
+                                }
+                            } else {
+                                let enemy = combat::enemy_at(&players, world_point, 0);
+                                let own = combat::entity_at(&players[..1], world_point);
+                                match (enemy, game_map.resource_at(world_point)) {
+                                    (Some(id), _) => { players[0].command_attack(id); },
+                                    (None, Some(id))
+                                        if players[0].order_gather(id, &game_map) => {},
+                                    _ if own.is_some_and(|id| players[0].order_construct(id)) => {},
+                                    _ => {
+                                        players[0].command_move(world_point, &game_map);
+                                    }
+                                }
+                            }
+                        }
                     }
                 }
                 _ => {}
@@ -211,35 +336,85 @@ fn main() {
         //Camera Movement 
         player_cam.move_cam(&game_map);
         
+        //Combat, before movement so cells freed by dead buildings reach the flow fields
+        combat::update(&mut players, &mut projectiles, &mut game_map);
+
+        //Workers mining and carrying resources back
+        gather::update(&mut players, &mut game_map);
+
+        //Workers walking to and building their sites
+        let targets = combat::collect_targets(&players);
+        players.iter_mut().for_each(|player| player.update_construction(&mut game_map,
+            &targets));
+
+        //Lifted off buildings flying around and landing
+        players.iter_mut().for_each(|player| player.update_flying(&mut game_map, &targets));
+
+        //Unit movement
+        players.iter_mut().for_each(|player| player.update_units(&game_map));
+        let bodies = steering::collect_bodies(&players);
+        let mut first_body: usize = 0;
+        players.iter_mut().for_each(|player| {
+            player.steer_units(&bodies, first_body, &game_map);
+            first_body += player.units.len();
+        });
+        game_map.occupancy_changes.clear();
+
         //Checks for completed constructions
+        players.iter_mut().for_each(|player| player.update_rallies());
         players.iter_mut().for_each(|player| 
-            player.check_completed_constructions(&tx_mgr));
+            player.check_completed_constructions(&tx_mgr, &game_map));
 
-        //Rendering segment (order: world -> objects -> buildings/units -> UI)
+        //Rendering segment (order: world -> objects -> buildings/units -> air -> projectiles -> UI)
         {
             let temp_players = players.to_owned();
 
             let _ = canvas.with_texture_canvas(&mut buffer, |texture_canvas| {
                 //Game world (map)
                 texture_canvas.clear();
-                texture_canvas.set_viewport(Rect::new(45, 45,
+                texture_canvas.set_viewport(Rect::new(WORLD_OFFSET, WORLD_OFFSET,
                     (game_map.world_sprites.len() + 1) as u32 *
                         game_map.world_sprites[0][0].loc_rect.w as u32 + 50,
                     game_map.world_sprites[0].len() as u32 *
                         game_map.world_sprites[0][0].loc_rect.h as u32 + 50));
 
                 game_map.render(texture_canvas, player_cam.viewport,
-                    players[0].placing_building, &tx_mgr);
+                    players[0].placing_building || editor.active, &tx_mgr);
                 
                 //World objects (decorations, obsticles, cliffs and similar)
-                for object in objects.iter() {
+                for object in game_map.objects.iter() {
                     object.render(&tx_mgr, texture_canvas);
                 }
 
+                //Mineral fields and gas geysers
+                for field in game_map.resource_fields.iter() {
+                    field.render(&tx_mgr, texture_canvas);
+                }
+
                 //Buildings/Units (all player or AI made buildings and units)
                 for player in temp_players.iter() {
                     player.render_owned(&tx_mgr, texture_canvas);
                 }
+
+                //Flyers, above ground units and buildings
+                for player in temp_players.iter() {
+                    player.render_air(&tx_mgr, texture_canvas);
+                }
+
+                //Projectiles, over everything else in the world
+                for projectile in projectiles.iter() {
+                    projectile.render(&tx_mgr, texture_canvas);
+                }
+
+                for player in temp_players.iter() {
+                    player.render_health_bars(texture_canvas);
+                }
+
+                players[0].render_selection(texture_canvas);
+
+                if editor.active {
+                    editor.render(&game_map, texture_canvas);
+                }
             });
 
             //Copy vieport from buffer
@@ -247,8 +422,34 @@ fn main() {
                 .expect("buffer coppy error");
         }
 
+        //Box selection
+        if let Some(start) = drag_start {
+            if let Some(drag_rect) = Rect::from_enclose_points(&[start, mouse_pos], None) {
+                canvas.set_draw_color(Color::RGBA(0, 255, 0, 255));
+                let _ = canvas.draw_rect(drag_rect);
+                canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+            }
+        }
+
+        //Ability targeting, area abilities show what they'll hit
+        match players[0].targeting {
+            Some(ability) => {
+                target_cursor.set();
+                if let ability::Targeting::Area(radius) = ability.get_targeting() {
+                    canvas.set_draw_color(Color::RGBA(255, 80, 0, 255));
+                    let _ = canvas.draw_rect(Rect::from_center(mouse_pos, radius * 2,
+                        radius * 2));
+                    canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+                }
+            },
+            None => { arrow_cursor.set(); }
+        }
+
         //UI
         players[0].render_ui(&tx_mgr, &mut canvas);
+        if editor.active {
+            editor.render_ui(&tx_mgr, &mut canvas);
+        }
 
         canvas.present();
         
@@ -0,0 +1,507 @@
+use sdl2::rect::{Point, Rect};
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::datafile::{self, DataError, expect_args, parse_num};
+use crate::general::Collidable;
+use crate::resources::ResourceKind;
+use crate::sprite::TextureType;
+
+//Map file format (plain text, one directive per line, '#' starts a comment):
+//
+//  version 1
+//  name <map name>
+//  size <width in tiles> <height in tiles>
+//  start <x> <y>                                   (pixels, one line per player)
+//  object <texture> <collidable> <x> <y> <w> <h>   (texture is "filler" or "tile:<index>")
+//  resource <minerals|gas> <x> <y> <amount>        (top left corner in pixels)
+//  tiles
+//  <height lines of width tile indices, top row first>
+pub const MAP_FORMAT_VERSION: u32 = 1;
+pub const TILE_SIZE: i32 = 50;
+
+pub const GRASS_TILE: i32 = 1;
+pub const DIRT_TILE: i32 = 2;
+pub const PLACEABLE_TILES: [i32; 2] = [GRASS_TILE, DIRT_TILE];
+
+#[derive(Debug)]
+pub enum MapError {
+    File(DataError),
+    MissingSize,
+    MissingTiles,
+    InvalidTile { x: usize, y: usize, index: i32 },
+    OutOfBounds { line: usize, what: String },
+    NoStartLocations,
+}
+
+impl fmt::Display for MapError {
+    fn fmt<'f>(&'f self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::File(err) => { write!(f, "{}", err) },
+            MapError::MissingSize => {
+                write!(f, "map file has no 'size' line before its tiles")
+            },
+            MapError::MissingTiles => {
+                write!(f, "map file has no 'tiles' section")
+            },
+            MapError::InvalidTile { x, y, index } => {
+                write!(f, "unknown tile index {} at ({}, {})", index, x, y)
+            },
+            MapError::OutOfBounds { line, what } => {
+                write!(f, "line {}: {} lies outside the map", line, what)
+            },
+            MapError::NoStartLocations => {
+                write!(f, "map file has no start locations")
+            },
+        }
+    }
+}
+
+impl std::error::Error for MapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapError::File(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DataError> for MapError {
+    fn from(err: DataError) -> MapError {
+        MapError::File(err)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MapObject {
+    pub t_type: TextureType,
+    pub collider: Rect,
+    pub collider_type: Collidable,
+}
+
+#[derive(Clone, Copy)]
+pub struct MapResource {
+    pub kind: ResourceKind,
+    pub location: Point,
+    pub amount: i32,
+}
+
+impl MapResource {
+    pub fn get_rect<'f>(&'f self) -> Rect {
+        Rect::new(self.location.x, self.location.y, self.kind.get_w(), self.kind.get_h())
+    }
+}
+
+//On-disk representation of a map, tiles are indexed [x][y] like World::world_encode
+#[derive(Clone)]
+pub struct MapFile {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<Vec<i32>>,
+    pub start_locations: Vec<Point>,
+    pub objects: Vec<MapObject>,
+    pub resources: Vec<MapResource>,
+}
+
+impl MapFile {
+    pub fn load<'f>(path: &'f Path) -> Result<MapFile, MapError> {
+        MapFile::parse(&datafile::read(path)?)
+    }
+
+    pub fn parse<'f>(contents: &'f str) -> Result<MapFile, MapError> {
+        let mut lines = datafile::lines(contents);
+        datafile::check_version(&mut lines, MAP_FORMAT_VERSION)?;
+
+        let mut map = MapFile {
+            name: String::new(),
+            width: 0,
+            height: 0,
+            tiles: vec![],
+            start_locations: vec![],
+            objects: vec![],
+            resources: vec![],
+        };
+        let mut object_lines: Vec<usize> = vec![];
+        let mut resource_lines: Vec<usize> = vec![];
+        let mut start_lines: Vec<usize> = vec![];
+        let mut has_tiles = false;
+
+        while let Some((line_nr, line)) = lines.next() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[0] {
+                "name" => {
+                    map.name = line["name".len()..].trim().to_string();
+                },
+                "size" => {
+                    expect_args(line_nr, &parts, 2)?;
+                    map.width = parse_num(line_nr, parts[1])?;
+                    map.height = parse_num(line_nr, parts[2])?;
+                    if map.width == 0 || map.height == 0 {
+                        return Err(DataError::Parse {
+                            line: line_nr,
+                            message: "map size can't be zero".to_string(),
+                        }.into());
+                    }
+                },
+                "start" => {
+                    expect_args(line_nr, &parts, 2)?;
+                    map.start_locations.push(Point::new(
+                        parse_num(line_nr, parts[1])?,
+                        parse_num(line_nr, parts[2])?));
+                    start_lines.push(line_nr);
+                },
+                "object" => {
+                    expect_args(line_nr, &parts, 6)?;
+                    map.objects.push(MapObject {
+                        t_type: parse_texture(line_nr, parts[1])?,
+                        collider_type: Collidable::from_str(parts[2]).map_err(|_|
+                            DataError::Parse {
+                                line: line_nr,
+                                message: format!("unknown collidable type '{}'", parts[2]),
+                            })?,
+                        collider: Rect::new(
+                            parse_num(line_nr, parts[3])?,
+                            parse_num(line_nr, parts[4])?,
+                            parse_num(line_nr, parts[5])?,
+                            parse_num(line_nr, parts[6])?),
+                    });
+                    object_lines.push(line_nr);
+                },
+                "resource" => {
+                    expect_args(line_nr, &parts, 4)?;
+                    let amount: i32 = parse_num(line_nr, parts[4])?;
+                    if amount <= 0 {
+                        return Err(DataError::Parse {
+                            line: line_nr,
+                            message: "resource amount must be above zero".to_string(),
+                        }.into());
+                    }
+                    map.resources.push(MapResource {
+                        kind: ResourceKind::from_str(parts[1]).map_err(|_|
+                            DataError::Parse {
+                                line: line_nr,
+                                message: format!("unknown resource '{}'", parts[1]),
+                            })?,
+                        location: Point::new(
+                            parse_num(line_nr, parts[2])?,
+                            parse_num(line_nr, parts[3])?),
+                        amount,
+                    });
+                    resource_lines.push(line_nr);
+                },
+                "tiles" => {
+                    if map.width == 0 {
+                        return Err(MapError::MissingSize);
+                    }
+
+                    map.tiles = vec![vec![0; map.height]; map.width];
+                    let mut y: usize = 0;
+                    while y < map.height {
+                        let (row_nr, row) = match lines.next() {
+                            Some(next) => next,
+                            None => {
+                                return Err(DataError::Parse {
+                                    line: line_nr,
+                                    message: format!("expected {} tile rows, found {}",
+                                        map.height, y),
+                                }.into());
+                            }
+                        };
+
+                        let row: Vec<&str> = row.split_whitespace().collect();
+                        if row.len() != map.width {
+                            return Err(DataError::Parse {
+                                line: row_nr,
+                                message: format!("expected {} tiles, found {}",
+                                    map.width, row.len()),
+                            }.into());
+                        }
+
+                        let mut x: usize = 0;
+                        while x < map.width {
+                            let index: i32 = parse_num(row_nr, row[x])?;
+                            if !PLACEABLE_TILES.contains(&index) {
+                                return Err(MapError::InvalidTile { x, y, index });
+                            }
+                            map.tiles[x][y] = index;
+                            x += 1;
+                        }
+                        y += 1;
+                    }
+                    has_tiles = true;
+                },
+                other => {
+                    return Err(DataError::Parse {
+                        line: line_nr,
+                        message: format!("unknown directive '{}'", other),
+                    }.into());
+                }
+            }
+        }
+
+        if !has_tiles {
+            return Err(MapError::MissingTiles);
+        }
+
+        if map.start_locations.is_empty() {
+            return Err(MapError::NoStartLocations);
+        }
+
+        let bounds = map.get_bounds();
+        for (start, line_nr) in map.start_locations.iter().zip(start_lines) {
+            if !bounds.contains_point(*start) {
+                return Err(MapError::OutOfBounds {
+                    line: line_nr,
+                    what: format!("start location ({}, {})", start.x, start.y),
+                });
+            }
+        }
+
+        for (object, line_nr) in map.objects.iter().zip(object_lines) {
+            if !bounds.contains_rect(object.collider) {
+                return Err(MapError::OutOfBounds {
+                    line: line_nr,
+                    what: "object".to_string(),
+                });
+            }
+        }
+
+        for (resource, line_nr) in map.resources.iter().zip(resource_lines) {
+            if !bounds.contains_rect(resource.get_rect()) {
+                return Err(MapError::OutOfBounds {
+                    line: line_nr,
+                    what: format!("{} field", resource.kind),
+                });
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn save<'f>(&'f self, path: &'f Path) -> Result<(), MapError> {
+        fs::write(path, self.to_text()).map_err(|source| DataError::Io {
+            path: path.to_path_buf(),
+            source,
+        }.into())
+    }
+
+    pub fn to_text<'f>(&'f self) -> String {
+        let mut text = String::from("# Random RTS map file, see src/map.rs for the format\n");
+        text.push_str(format!("version {}\n", MAP_FORMAT_VERSION).as_str());
+        if !self.name.is_empty() {
+            text.push_str(format!("name {}\n", self.name).as_str());
+        }
+        text.push_str(format!("size {} {}\n", self.width, self.height).as_str());
+
+        for start in self.start_locations.iter() {
+            text.push_str(format!("start {} {}\n", start.x, start.y).as_str());
+        }
+
+        for object in self.objects.iter() {
+            text.push_str(format!("object {} {} {} {} {} {}\n",
+                texture_to_string(object.t_type), object.collider_type,
+                object.collider.x, object.collider.y,
+                object.collider.w, object.collider.h).as_str());
+        }
+
+        for resource in self.resources.iter() {
+            text.push_str(format!("resource {} {} {} {}\n", resource.kind,
+                resource.location.x, resource.location.y, resource.amount).as_str());
+        }
+
+        text.push_str("tiles\n");
+        let mut y: usize = 0;
+        while y < self.height {
+            let row: Vec<String> = self.tiles.iter()
+                .map(|column| column[y].to_string())
+                .collect();
+            text.push_str(row.join(" ").as_str());
+            text.push('\n');
+            y += 1;
+        }
+
+        text
+    }
+
+    //Map area in pixels
+    pub fn get_bounds<'f>(&'f self) -> Rect {
+        Rect::new(0, 0, self.width as u32 * TILE_SIZE as u32,
+            self.height as u32 * TILE_SIZE as u32)
+    }
+}
+
+fn texture_to_string(t_type: TextureType) -> String {
+    match t_type {
+        TextureType::World { tile_index } => { format!("tile:{}", tile_index) },
+        _ => { "filler".to_string() }
+    }
+}
+
+fn parse_texture<'f>(line: usize, value: &'f str) -> Result<TextureType, DataError> {
+    if value == "filler" {
+        return Ok(TextureType::Filler);
+    }
+
+    if let Some(index) = value.strip_prefix("tile:") {
+        let tile_index: i32 = parse_num(line, index)?;
+        if PLACEABLE_TILES.contains(&tile_index) {
+            return Ok(TextureType::World { tile_index: tile_index as usize });
+        }
+    }
+
+    Err(DataError::Parse {
+        line,
+        message: format!("unknown object texture '{}'", value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafile::testing::{self, swapped, line_of};
+
+    //4x3 tiles, 200x150 pixels
+    const SMALL: &str = "\
+version 1
+name Small
+size 4 3
+start 25 25
+start 175 125
+object filler GroundCollidable 100 0 50 50
+object tile:2 AirCollidable 0 100 50 50
+resource minerals 150 0 500
+resource gas 0 0 1000
+tiles
+1 1 2 1
+1 2 2 1
+1 1 1 1
+";
+
+    fn parse_error<'f>(contents: &'f str) -> MapError {
+        MapFile::parse(contents).err().expect("map parsed without an error")
+    }
+
+    fn assert_parse_error<'f>(contents: &'f str, line: usize, expected: &'f str) {
+        match parse_error(contents) {
+            MapError::File(err) => { testing::assert_parse_error(&err, line, expected); },
+            err => { panic!("expected a parse error, got '{}'", err); }
+        }
+    }
+
+    #[test]
+    fn small_map_parses() {
+        let map = MapFile::parse(SMALL).unwrap();
+
+        assert_eq!(map.name, "Small");
+        assert_eq!((map.width, map.height), (4, 3));
+        assert_eq!(map.tiles[2][0], DIRT_TILE);
+        assert_eq!(map.tiles[1][1], DIRT_TILE);
+        assert_eq!(map.tiles[3][2], GRASS_TILE);
+        assert_eq!(map.start_locations, vec![Point::new(25, 25), Point::new(175, 125)]);
+        assert!(map.objects[0].t_type == TextureType::Filler);
+        assert!(map.objects[1].t_type == TextureType::World { tile_index: 2 });
+        assert_eq!(map.objects[1].collider, Rect::new(0, 100, 50, 50));
+        assert_eq!(map.resources[1].kind, ResourceKind::Gas);
+        assert_eq!(map.resources[1].amount, 1000);
+    }
+
+    #[test]
+    fn round_trip() {
+        let map = MapFile::parse(SMALL).unwrap();
+        let again = MapFile::parse(&map.to_text()).unwrap();
+
+        assert_eq!(again.name, map.name);
+        assert_eq!((again.width, again.height), (map.width, map.height));
+        assert_eq!(again.tiles, map.tiles);
+        assert_eq!(again.start_locations, map.start_locations);
+        assert_eq!(again.objects.len(), map.objects.len());
+        for (object, original) in again.objects.iter().zip(map.objects.iter()) {
+            assert!(object.t_type == original.t_type);
+            assert!(object.collider_type == original.collider_type);
+            assert_eq!(object.collider, original.collider);
+        }
+        assert_eq!(again.resources.len(), map.resources.len());
+        for (resource, original) in again.resources.iter().zip(map.resources.iter()) {
+            assert_eq!(resource.kind, original.kind);
+            assert_eq!(resource.location, original.location);
+            assert_eq!(resource.amount, original.amount);
+        }
+        assert_eq!(again.to_text(), map.to_text());
+    }
+
+    #[test]
+    fn shipped_map_parses() {
+        let map = MapFile::parse(include_str!("../assets/maps/default.map")).unwrap();
+
+        assert!(map.start_locations.len() >= 2);
+        assert_eq!(map.tiles.len(), map.width);
+    }
+
+    #[test]
+    fn missing_version() {
+        let contents = swapped(SMALL, "version 1\n", "");
+
+        assert!(matches!(parse_error(&contents), MapError::File(DataError::MissingVersion)));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let contents = swapped(SMALL, "version 1", "version 2");
+
+        assert!(matches!(parse_error(&contents),
+            MapError::File(DataError::UnsupportedVersion { found: 2, expected: 1 })));
+    }
+
+    #[test]
+    fn bad_tile() {
+        let contents = swapped(SMALL, "1 2 2 1", "1 2 7 1");
+
+        assert!(matches!(parse_error(&contents), MapError::InvalidTile { x: 2, y: 1, index: 7 }));
+    }
+
+    #[test]
+    fn short_tile_row() {
+        let contents = swapped(SMALL, "1 2 2 1", "1 2 2");
+
+        assert_parse_error(&contents, line_of(&contents, "1 2 2"), "expected 4 tiles, found 3");
+    }
+
+    #[test]
+    fn object_out_of_bounds() {
+        let contents = swapped(SMALL, "object filler GroundCollidable 100 0",
+            "object filler GroundCollidable 175 0");
+
+        match parse_error(&contents) {
+            MapError::OutOfBounds { line, what } => {
+                assert_eq!(line, line_of(&contents, "object filler"));
+                assert_eq!(what, "object");
+            },
+            err => { panic!("expected an object out of bounds, got '{}'", err); }
+        }
+    }
+
+    #[test]
+    fn start_out_of_bounds() {
+        let contents = swapped(SMALL, "start 175 125", "start 175 150");
+
+        assert!(matches!(parse_error(&contents), MapError::OutOfBounds { line: 5, .. }));
+    }
+
+    #[test]
+    fn unknown_directive() {
+        let contents = swapped(SMALL, "name Small", "title Small");
+
+        assert_parse_error(&contents, 2, "unknown directive 'title'");
+    }
+
+    #[test]
+    fn missing_tiles() {
+        let end = SMALL.find("tiles").unwrap();
+
+        assert!(matches!(parse_error(&SMALL[..end]), MapError::MissingTiles));
+    }
+}
@@ -0,0 +1,305 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sdl2::rect::{Point, Rect};
+
+use std::fmt;
+
+use crate::general::Collidable;
+use crate::map::{MapFile, MapObject, MapResource, TILE_SIZE, GRASS_TILE, DIRT_TILE};
+use crate::resources::ResourceKind;
+use crate::sprite::TextureType;
+
+const START_FOOTPRINT: i32 = 3; // Command Centre size in tiles
+const START_CLEARANCE: i32 = 3;
+const MAX_BLOB_SIZE: usize = 40;
+const MINERAL_LINE: i32 = 6; // Mineral patches per base, in a column right of it
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Symmetry {
+    Mirror,
+    Rotational,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapGenParams {
+    pub width: usize,
+    pub height: usize,
+    pub players: usize,
+    pub symmetry: Symmetry,
+    pub dirt_ratio: f32,
+    pub obstacles: usize,
+}
+
+impl MapGenParams {
+    pub fn new(width: usize, height: usize, players: usize, symmetry: Symmetry) -> MapGenParams {
+        MapGenParams {
+            width,
+            height,
+            players,
+            symmetry,
+            dirt_ratio: 0.2,
+            obstacles: 6,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MapGenError {
+    UnsupportedPlayerCount(usize),
+    MapTooSmall,
+    RotationNeedsSquareMap,
+    InvalidDirtRatio(f32),
+}
+
+impl fmt::Display for MapGenError {
+    fn fmt<'f>(&'f self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapGenError::UnsupportedPlayerCount(players) => {
+                write!(f, "can't generate a fair map for {} players (use 2 or 4)", players)
+            },
+            MapGenError::MapTooSmall => {
+                write!(f, "map is too small to fit every start location")
+            },
+            MapGenError::RotationNeedsSquareMap => {
+                write!(f, "4 player rotational symmetry needs a square map")
+            },
+            MapGenError::InvalidDirtRatio(ratio) => {
+                write!(f, "dirt ratio {} must be between 0 and 1", ratio)
+            },
+        }
+    }
+}
+
+impl std::error::Error for MapGenError {}
+
+//Generates a map where every player's surroundings are an exact mirror or
+//rotation of each other, the same seed and params always give the same map.
+//ChaCha8 is used over StdRng since StdRng's output may change between rand versions.
+pub fn generate<'f>(seed: u64, params: &'f MapGenParams) -> Result<MapFile, MapGenError> {
+    validate(params)?;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let w = params.width as i32;
+    let h = params.height as i32;
+    let mut map = MapFile {
+        name: format!("Generated {}p {}", params.players, seed),
+        width: params.width,
+        height: params.height,
+        tiles: vec![vec![GRASS_TILE; params.height]; params.width],
+        start_locations: vec![],
+        objects: vec![],
+        resources: vec![],
+    };
+
+    //Start locations, one base in the top left corner copied to every player
+    let base = Rect::new(
+        rng.gen_range(2..(w / 4).max(3)),
+        rng.gen_range(2..(h / 4).max(3)),
+        START_FOOTPRINT as u32, START_FOOTPRINT as u32);
+    let bases = images_of_rect(base, params);
+    map.start_locations = bases.iter()
+        .map(|base| Point::new(base.x * TILE_SIZE, base.y * TILE_SIZE))
+        .collect();
+
+    //Resources, a mineral line and a geyser inside every base's clearance
+    let mut fields: Vec<(ResourceKind, Rect)> = vec![];
+    let mut i: i32 = 0;
+    while i < MINERAL_LINE {
+        fields.push((ResourceKind::Minerals,
+            Rect::new(base.right() + 2, base.y - 1 + i, 1, 1)));
+        i += 1;
+    }
+    fields.push((ResourceKind::Gas, Rect::new(base.x, base.bottom() + 1, 2, 2)));
+
+    for (kind, field) in fields {
+        for image in images_of_rect(field, params) {
+            map.resources.push(MapResource {
+                kind,
+                location: Point::new(image.x * TILE_SIZE, image.y * TILE_SIZE),
+                amount: kind.get_default_amount(),
+            });
+        }
+    }
+
+    //Dirt patches, grown as random walks until the ratio is met
+    let target = (params.dirt_ratio * (params.width * params.height) as f32) as usize;
+    let mut dirt: usize = 0;
+    while dirt < target {
+        let mut x = rng.gen_range(0..w);
+        let mut y = rng.gen_range(0..h);
+        let blob = rng.gen_range(1..=MAX_BLOB_SIZE);
+
+        let mut i: usize = 0;
+        while i < blob && dirt < target {
+            for (ix, iy) in images_of_cell(x, y, params) {
+                if map.tiles[ix as usize][iy as usize] != DIRT_TILE {
+                    map.tiles[ix as usize][iy as usize] = DIRT_TILE;
+                    dirt += 1;
+                }
+            }
+
+            x = (x + rng.gen_range(-1..=1)).clamp(0, w - 1);
+            y = (y + rng.gen_range(-1..=1)).clamp(0, h - 1);
+            i += 1;
+        }
+    }
+
+    //Obstacles, kept clear of every start location
+    let clearances: Vec<Rect> = bases.iter()
+        .map(|base| Rect::new(base.x - START_CLEARANCE, base.y - START_CLEARANCE,
+            (START_FOOTPRINT + START_CLEARANCE * 2) as u32,
+            (START_FOOTPRINT + START_CLEARANCE * 2) as u32))
+        .collect();
+
+    let mut placed: usize = 0;
+    let mut attempts: usize = 0;
+    while placed < params.obstacles && attempts < params.obstacles * 20 {
+        attempts += 1;
+        let size = rng.gen_range(1..=2);
+        let obstacle = Rect::new(rng.gen_range(0..=w - size), rng.gen_range(0..=h - size),
+            size as u32, size as u32);
+
+        let obstacle_images = images_of_rect(obstacle, params);
+        if obstacle_images.iter().any(|image|
+                clearances.iter().any(|clear| clear.has_intersection(*image))) {
+            continue;
+        }
+
+        for image in obstacle_images {
+            map.objects.push(MapObject {
+                t_type: TextureType::Filler,
+                collider: Rect::new(image.x * TILE_SIZE, image.y * TILE_SIZE,
+                    image.width() * TILE_SIZE as u32, image.height() * TILE_SIZE as u32),
+                collider_type: Collidable::GroundCollidable,
+            });
+        }
+        placed += 1;
+    }
+
+    Ok(map)
+}
+
+fn validate<'f>(params: &'f MapGenParams) -> Result<(), MapGenError> {
+    if params.players != 2 && params.players != 4 {
+        return Err(MapGenError::UnsupportedPlayerCount(params.players));
+    }
+
+    if !(0.0..=1.0).contains(&params.dirt_ratio) {
+        return Err(MapGenError::InvalidDirtRatio(params.dirt_ratio));
+    }
+
+    let min_size = ((START_FOOTPRINT + START_CLEARANCE) * 4) as usize;
+    if params.width < min_size || params.height < min_size {
+        return Err(MapGenError::MapTooSmall);
+    }
+
+    if params.players == 4 && params.symmetry == Symmetry::Rotational &&
+            params.width != params.height {
+        return Err(MapGenError::RotationNeedsSquareMap);
+    }
+
+    Ok(())
+}
+
+//Every position a tile is copied to, one per player
+fn images_of_cell<'f>(x: i32, y: i32, params: &'f MapGenParams) -> Vec<(i32, i32)> {
+    images_of_rect(Rect::new(x, y, 1, 1), params).iter()
+        .map(|rect| (rect.x, rect.y))
+        .collect()
+}
+
+//Rect is in tiles, duplicates (e.g. on the mirror axis) are dropped
+fn images_of_rect<'f>(rect: Rect, params: &'f MapGenParams) -> Vec<Rect> {
+    let w = params.width as i32;
+    let h = params.height as i32;
+    let mirror_x = Rect::new(w - rect.right(), rect.y, rect.width(), rect.height());
+    let mirror_y = Rect::new(rect.x, h - rect.bottom(), rect.width(), rect.height());
+    let rotate_180 = Rect::new(w - rect.right(), h - rect.bottom(),
+        rect.width(), rect.height());
+
+    let mut images = match (params.symmetry, params.players) {
+        (Symmetry::Mirror, 2) => { vec![rect, mirror_x] },
+        (Symmetry::Rotational, 2) => { vec![rect, rotate_180] },
+        (Symmetry::Mirror, _) => { vec![rect, mirror_x, rotate_180, mirror_y] },
+        (Symmetry::Rotational, _) => {
+            vec![
+                rect,
+                Rect::new(w - rect.bottom(), rect.x, rect.height(), rect.width()),
+                rotate_180,
+                Rect::new(rect.y, h - rect.right(), rect.height(), rect.width()),
+            ]
+        },
+    };
+
+    let mut i: usize = 0;
+    while i < images.len() {
+        if images[..i].contains(&images[i]) {
+            images.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    images
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Rows top to bottom, '.' for grass and '#' for dirt
+    fn tile_rows<'f>(map: &'f MapFile) -> Vec<String> {
+        (0..map.height)
+            .map(|y| map.tiles.iter()
+                .map(|column| if column[y] == DIRT_TILE { '#' } else { '.' })
+                .collect())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let mut params = MapGenParams::new(24, 24, 2, Symmetry::Mirror);
+        params.dirt_ratio = 0.1;
+        params.obstacles = 2;
+        let map = generate(7, &params).unwrap();
+
+        assert_eq!(map.to_text(), generate(7, &params).unwrap().to_text());
+        assert_ne!(map.to_text(), generate(8, &params).unwrap().to_text());
+
+        //Pinned, so any change to the generator or its rng shows up here
+        assert_eq!(map.start_locations, vec![Point::new(150, 100), Point::new(900, 100)]);
+        let objects: Vec<Rect> = map.objects.iter().map(|object| object.collider).collect();
+        assert_eq!(objects, vec![
+            Rect::new(600, 250, 100, 100),
+            Rect::new(500, 250, 100, 100),
+            Rect::new(500, 400, 50, 50),
+            Rect::new(650, 400, 50, 50),
+        ]);
+        assert_eq!(tile_rows(&map), vec![
+            "......#.########.#......",
+            "......###.####.###......",
+            "........#..##..#........",
+            "........##....##........",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            ".......##......##.......",
+            ".........#....#.........",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "........................",
+            "....#...#......#...#....",
+            "...#####........#####...",
+            "....#####......#####....",
+        ]);
+    }
+}
@@ -0,0 +1,352 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+//Kept free of SDL types so it can run on any grid, not just World::grid
+pub const STRAIGHT_COST: u32 = 10;
+pub const DIAGONAL_COST: u32 = 14;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1),
+    (1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+
+//Rectangle of grid cells
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CellRegion {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+impl CellRegion {
+    pub fn new(x: usize, y: usize, w: usize, h: usize) -> CellRegion {
+        CellRegion { x, y, w, h }
+    }
+
+    pub fn contains<'f>(&'f self, cell: (usize, usize)) -> bool {
+        cell.0 >= self.x && cell.0 < self.x + self.w &&
+            cell.1 >= self.y && cell.1 < self.y + self.h
+    }
+
+    pub fn intersects<'f>(&'f self, other: &'f CellRegion) -> bool {
+        self.x < other.x + other.w && other.x < self.x + self.w &&
+            self.y < other.y + other.h && other.y < self.y + self.h
+    }
+
+    pub fn union<'f>(&'f self, other: CellRegion) -> CellRegion {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        CellRegion::new(x, y,
+            (self.x + self.w).max(other.x + other.w) - x,
+            (self.y + self.h).max(other.y + other.h) - y)
+    }
+
+    //Grows by margin on every side, clamped to a width x height grid
+    pub fn expand<'f>(&'f self, margin: usize, width: usize, height: usize) -> CellRegion {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+        CellRegion::new(x, y,
+            (self.x + self.w + margin).min(width) - x,
+            (self.y + self.h + margin).min(height) - y)
+    }
+}
+
+pub trait PathGrid {
+    fn width<'f>(&'f self) -> usize;
+    fn height<'f>(&'f self) -> usize;
+    fn is_blocked<'f>(&'f self, x: usize, y: usize) -> bool;
+
+    //A footprint of size n covers n*n cells with (x, y) as its top left cell
+    fn fits<'f>(&'f self, x: i32, y: i32, footprint: usize) -> bool {
+        if x < 0 || y < 0 || x as usize + footprint > self.width() ||
+                y as usize + footprint > self.height() {
+            return false;
+        }
+
+        let mut i: usize = 0;
+        while i < footprint {
+            let mut j: usize = 0;
+            while j < footprint {
+                if self.is_blocked(x as usize + i, y as usize + j) {
+                    return false;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+
+        true
+    }
+}
+
+//Synthetic grids, indexed [x][y] with true meaning blocked
+impl PathGrid for Vec<Vec<bool>> {
+    fn width<'f>(&'f self) -> usize {
+        self.len()
+    }
+
+    fn height<'f>(&'f self) -> usize {
+        if self.is_empty() { 0 } else { self[0].len() }
+    }
+
+    fn is_blocked<'f>(&'f self, x: usize, y: usize) -> bool {
+        self[x][y]
+    }
+}
+
+//Closest cell to target where the footprint fits, searched in growing squares
+pub fn nearest_free_cell<'f, G: PathGrid>(grid: &'f G, target: (usize, usize),
+        footprint: usize) -> Option<(usize, usize)> {
+    let max_ring = grid.width().max(grid.height()) as i32;
+    let (tx, ty) = (target.0 as i32, target.1 as i32);
+
+    let mut ring: i32 = 0;
+    while ring < max_ring {
+        let mut best: Option<(u32, (usize, usize))> = None;
+        let mut x = tx - ring;
+        while x <= tx + ring {
+            let mut y = ty - ring;
+            while y <= ty + ring {
+                let on_ring = (x - tx).abs() == ring || (y - ty).abs() == ring;
+                if on_ring && grid.fits(x, y, footprint) {
+                    let cell = (x as usize, y as usize);
+                    let cost = heuristic(cell, target);
+                    match best {
+                        Some((best_cost, _)) if best_cost <= cost => {},
+                        _ => { best = Some((cost, cell)); }
+                    }
+                }
+                y += 1;
+            }
+            x += 1;
+        }
+
+        if let Some((_, cell)) = best {
+            return Some(cell);
+        }
+        ring += 1;
+    }
+
+    None
+}
+
+//Octile distance, exact on an open 8-connected grid
+pub fn heuristic(from: (usize, usize), to: (usize, usize)) -> u32 {
+    let dx = (from.0 as i32 - to.0 as i32).unsigned_abs();
+    let dy = (from.1 as i32 - to.1 as i32).unsigned_abs();
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+//A* from start to goal (top left cells of the footprint). Diagonal moves may not
+//cut a blocked corner. If the goal can't be reached the path leads to the closest
+//reachable cell instead, None means the start itself is blocked.
+//The returned path starts after start and ends at the goal.
+pub fn find_path<'f, G: PathGrid>(grid: &'f G, start: (usize, usize), goal: (usize, usize),
+        footprint: usize) -> Option<Vec<(usize, usize)>> {
+    let width = grid.width();
+    let height = grid.height();
+    let footprint = footprint.max(1);
+
+    if !grid.fits(start.0 as i32, start.1 as i32, footprint) {
+        return None;
+    }
+
+    let index = |cell: (usize, usize)| cell.0 * height + cell.1;
+    let mut g_score: Vec<u32> = vec![u32::MAX; width * height];
+    let mut came_from: Vec<Option<(usize, usize)>> = vec![None; width * height];
+    let mut closed: Vec<bool> = vec![false; width * height];
+    let mut open: BinaryHeap<Reverse<(u32, u32, usize, usize)>> = BinaryHeap::new();
+
+    g_score[index(start)] = 0;
+    open.push(Reverse((heuristic(start, goal), heuristic(start, goal), start.0, start.1)));
+    let mut closest = (heuristic(start, goal), start);
+
+    while let Some(Reverse((_, h, x, y))) = open.pop() {
+        let current = (x, y);
+        if closed[index(current)] {
+            continue;
+        }
+        closed[index(current)] = true;
+
+        if h < closest.0 {
+            closest = (h, current);
+        }
+        if current == goal {
+            break;
+        }
+
+        for (dx, dy) in NEIGHBOURS {
+            let nx = x as i32 + dx;
+            let ny = y as i32 + dy;
+            if !grid.fits(nx, ny, footprint) {
+                continue;
+            }
+
+            let diagonal = dx != 0 && dy != 0;
+            if diagonal && (!grid.fits(x as i32 + dx, y as i32, footprint) ||
+                    !grid.fits(x as i32, y as i32 + dy, footprint)) {
+                continue;
+            }
+
+            let next = (nx as usize, ny as usize);
+            let cost = g_score[index(current)] +
+                if diagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+            if cost < g_score[index(next)] {
+                g_score[index(next)] = cost;
+                came_from[index(next)] = Some(current);
+                let next_h = heuristic(next, goal);
+                open.push(Reverse((cost + next_h, next_h, next.0, next.1)));
+            }
+        }
+    }
+
+    let mut path: Vec<(usize, usize)> = vec![];
+    let mut current = closest.1;
+    while current != start {
+        path.push(current);
+        current = came_from[index(current)].unwrap();
+    }
+    path.reverse();
+
+    Some(path)
+}
+
+//Drops cells in the middle of straight runs, leaving only the turns and the end
+pub fn to_waypoints<'f>(path: &'f [(usize, usize)], start: (usize, usize)) -> Vec<(usize, usize)> {
+    let mut waypoints: Vec<(usize, usize)> = vec![];
+    let mut previous = start;
+    let mut i: usize = 0;
+
+    while i < path.len() {
+        let direction = (path[i].0 as i32 - previous.0 as i32,
+            path[i].1 as i32 - previous.1 as i32);
+        let turns = match path.get(i + 1) {
+            Some(next) => {
+                (next.0 as i32 - path[i].0 as i32, next.1 as i32 - path[i].1 as i32) !=
+                    direction
+            },
+            None => { true }
+        };
+
+        if turns {
+            waypoints.push(path[i]);
+        }
+        previous = path[i];
+        i += 1;
+    }
+
+    waypoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Open width x height grid with the given cells blocked
+    fn grid(width: usize, height: usize, blocked: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        let mut grid = vec![vec![false; height]; width];
+        for (x, y) in blocked {
+            grid[*x][*y] = true;
+        }
+        grid
+    }
+
+    //Every cell of column x blocked except the ones in gaps
+    fn wall(x: usize, height: usize, gaps: &[usize]) -> Vec<(usize, usize)> {
+        (0..height).filter(|y| !gaps.contains(y)).map(|y| (x, y)).collect()
+    }
+
+    #[test]
+    fn straight_path() {
+        let grid = grid(10, 5, &[]);
+        let path = find_path(&grid, (0, 2), (5, 2), 1).unwrap();
+
+        assert_eq!(path, vec![(1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]);
+        assert_eq!(to_waypoints(&path, (0, 2)), vec![(5, 2)]);
+    }
+
+    #[test]
+    fn detours_around_a_wall() {
+        let grid = grid(7, 7, &wall(3, 7, &[6]));
+        let path = find_path(&grid, (0, 0), (6, 0), 1).unwrap();
+
+        assert_eq!(path.last(), Some(&(6, 0)));
+        assert!(path.contains(&(3, 6)));
+        assert!(path.iter().all(|(x, y)| !grid[*x][*y]));
+    }
+
+    #[test]
+    fn diagonal_cutting_a_corner_is_refused() {
+        let grid = grid(3, 3, &[(1, 0)]);
+        let path = find_path(&grid, (0, 0), (1, 1), 1).unwrap();
+
+        assert_eq!(path, vec![(0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn open_diagonal_is_taken() {
+        let grid = grid(3, 3, &[]);
+
+        assert_eq!(find_path(&grid, (0, 0), (1, 1), 1).unwrap(), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn large_footprint_does_not_fit_through_a_narrow_gap() {
+        let grid = grid(7, 7, &wall(3, 7, &[3]));
+
+        let small = find_path(&grid, (0, 0), (5, 0), 1).unwrap();
+        assert_eq!(small.last(), Some(&(5, 0)));
+
+        //A 2x2 unit stops at the wall, x 1 is the last column where it fits
+        let large = find_path(&grid, (0, 0), (5, 0), 2).unwrap();
+        assert_eq!(large.last().map(|cell| cell.0), Some(1));
+    }
+
+    #[test]
+    fn unreachable_goal_leads_to_closest_reachable_cell() {
+        let grid = grid(7, 7, &wall(5, 7, &[]));
+        let path = find_path(&grid, (0, 3), (6, 3), 1).unwrap();
+
+        assert_eq!(path.last(), Some(&(4, 3)));
+    }
+
+    #[test]
+    fn blocked_start_has_no_path() {
+        let grid = grid(3, 3, &[(0, 0)]);
+
+        assert_eq!(find_path(&grid, (0, 0), (2, 2), 1), None);
+    }
+
+    #[test]
+    fn nearest_free_cell_is_the_target_when_free() {
+        let grid = grid(5, 5, &[]);
+
+        assert_eq!(nearest_free_cell(&grid, (2, 2), 1), Some((2, 2)));
+    }
+
+    #[test]
+    fn nearest_free_cell_steps_off_a_blocked_target() {
+        let grid = grid(5, 5, &[(2, 2)]);
+        let cell = nearest_free_cell(&grid, (2, 2), 1).unwrap();
+
+        assert_eq!(heuristic(cell, (2, 2)), STRAIGHT_COST);
+        assert!(!grid[cell.0][cell.1]);
+    }
+
+    #[test]
+    fn nearest_free_cell_fits_the_footprint() {
+        let grid = grid(6, 6, &wall(2, 6, &[]));
+        let cell = nearest_free_cell(&grid, (2, 2), 2).unwrap();
+
+        assert!(grid.fits(cell.0 as i32, cell.1 as i32, 2));
+        assert!(cell.0 == 0 || cell.0 == 3);
+    }
+
+    #[test]
+    fn full_grid_has_no_free_cell() {
+        let grid = vec![vec![true; 3]; 3];
+
+        assert_eq!(nearest_free_cell(&grid, (1, 1), 1), None);
+    }
+}
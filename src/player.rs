@@ -1,11 +1,25 @@
 use sdl2::render::WindowCanvas;
 use sdl2::rect::{Point, Rect};
+use sdl2::pixels::Color;
+use sdl2::keyboard::Keycode;
 
-use crate::building::{BuildingType, BuildingStatus};
+use std::collections::HashMap;
+
+use crate::building::{self, BuildingType, BuildingStatus, ConstructionType, Rally,
+    PlacementError};
 use crate::general::{Selectable, Renderable};
 use crate::sprite::{TextureManager, TextureType};
-use crate::ui::{ButtonFunction, UIProperties, XAlignment, YAlignment, UIManager};
+use crate::ui::{ButtonFunction, UIProperties, XAlignment, YAlignment, UIManager, Notification};
+use crate::resources::{ResourceBank, ResourceKind, ResourceField, ResourceError};
+use crate::gather;
+use crate::text;
 use crate::world::World;
+use crate::flowfield::{FlowField, FLOW_FIELD_GROUP_SIZE};
+use crate::steering::{self, Body};
+use crate::combat::{self, Target, Shot, Hit, WeaponTargets};
+use crate::ability::{self, AbilityKind, Targeting, Cast, CastTarget, CastError};
+use crate::projectile::{Projectile, ProjectileStats};
+
 use crate::{Sprite, Building, Faction, Unit, ui::{UiElement, Button}};
 use crate::general::{self, Selection};
 
@@ -17,7 +31,15 @@ pub struct Player {
     pub bottom_right_ui: Vec<UiElement>,
     pub selected: Selection,
     pub placing_building: bool,
-    pub construction_buttons: [Option<Button>; 16], 
+    pub builder: Option<u32>, // Worker that builds the ghost being placed
+    pub lander: Option<u32>, // Flying building the ghost picks a landing site for
+    pub queue_ui: Vec<UiElement>,
+    pub selected_units: Vec<usize>, // Box selection, selected holds the first of them
+    pub flow_fields: HashMap<u32, FlowField>,
+    pub next_flow_field_id: u32,
+    pub targeting: Option<AbilityKind>, // Ability waiting for the player to pick a target
+    pub resources: ResourceBank,
+    pub notification: Option<Notification>,
 }
 
 impl Player {
@@ -29,8 +51,16 @@ impl Player {
             units: vec![],
             faction,
             selected: Selection::None,
-            construction_buttons: [None; 16],
+            queue_ui: vec![],
+            selected_units: vec![],
+            flow_fields: HashMap::new(),
+            next_flow_field_id: 0,
+            targeting: None,
+            resources: ResourceBank::new(general::STARTING_MINERALS, general::STARTING_GAS),
+            notification: None,
             placing_building: false,
+            builder: None,
+            lander: None,
             bottom_right_ui: vec![UiElement::new(Sprite::new(
                 Rect::new(0, 0, 280, 280),
                 TextureType::UI { type_index: 1 }, 
@@ -70,115 +100,949 @@ impl Player {
             }
         }
 
-        new_p.construction_buttons[0] = general::gen_button(tx_mgr,
-            new_p.bottom_right_ui.to_owned(), ButtonFunction::PlaceCommandCentre, 0);
-        new_p.construction_buttons[1] = general::gen_button(tx_mgr,
-            new_p.bottom_right_ui.to_owned(), ButtonFunction::PlaceBarracks, 1);
-        new_p.construction_buttons[15] = general::gen_button(tx_mgr,
-            new_p.bottom_right_ui.to_owned(), ButtonFunction::Back, 15);
+        { // Production queue slots, above the button panels
+            let mut i: i32 = 0;
+            while i < general::MAX_QUEUE_LENGTH as i32 {
+                new_p.queue_ui.push(
+                    UiElement::new(Sprite::new(
+                        Rect::new(
+                            new_p.bottom_right_ui[0].collider.x + i * 54 + 6,
+                            new_p.bottom_right_ui[0].collider.y - 56,
+                            50, 50),
+                        TextureType::UI { type_index: 1 },
+                        tx_mgr ),
+                        None,
+                    1));
+                i += 1;
+            }
+        }
 
         return new_p;
     }
     
     //Building Interactions
     
-    pub fn check_completed_constructions<'f>(&'f mut self, tx_mgr: &'f TextureManager) {
-        let mut completed_cons: Vec<Building> = vec![];
+    pub fn check_completed_constructions<'f>(&'f mut self, tx_mgr: &'f TextureManager,
+            game_map: &'f World) {
+        let mut i: usize = 0;
+        while i < self.buildings.len() {
+            if self.buildings[i].construction_done() {
+                match self.buildings[i].production_queue[0].product {
+                    ConstructionType::Unit(unit_type) => {
+                        // Stays at the front of the queue until there's room to spawn
+                        if let Some(location) = self.find_spawn_location(i, game_map) {
+                            let completed_con = self.buildings[i].take_constructed();
+                            let mut unit = completed_con.train_unit(unit_type, location, tx_mgr);
+                            if let Some(rally) = self.buildings[i].rally {
+                                //Workers rallied onto a field they can mine start mining it
+                                let field = match rally {
+                                    Rally::Entity { id, .. } => game_map.resource_field(id)
+                                        .filter(|field| self.can_harvest(field)),
+                                    Rally::Point(..) => None,
+                                };
+                                match field {
+                                    Some(field) if unit_type.can_gather() => {
+                                        unit.gather_from(field);
+                                    },
+                                    _ => { unit.move_to(self.get_rally_point(rally), game_map); }
+                                }
+                            }
+                            self.units.push(unit);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
 
-        self.buildings.iter_mut().filter(|building| building.construction_done())
-            .for_each(|building| completed_cons.push(building.get_constructed(tx_mgr)));
-        
-        completed_cons.iter().for_each(|completed_con|
-            self.buildings.push(completed_con.to_owned()));
+    //Free grid cell next to a building that no unit is standing on
+    pub fn find_spawn_location<'f>(&'f self, building_index: usize, game_map: &'f World)
+            -> Option<Point> {
+        let unit_colliders: Vec<Rect> = self.units.iter()
+            .map(|unit| unit.collider)
+            .collect();
+
+        game_map.find_free_cell_near(self.buildings[building_index].collider, &unit_colliders)
     }
 
-    pub fn place_building<'f>(&'f mut self, game_map: &'f mut World) {
-        let index = self.selected.index();
-        let cell_x = self.buildings[index].x_in_cells();
-        let cell_y = self.buildings[index].y_in_cells();
-        let w_cells = self.buildings[index].width_in_cells();
-        let h_cells = self.buildings[index].height_in_cells();
-         
-        let mut i: i32 = 0;
-        while i < w_cells {
-            let mut j: i32 = 0;
-            while j < h_cells {
-                let ii = (cell_x + i) as usize;
-                let jj = (cell_y + j) as usize;
-                if game_map.grid[ii][jj].occupied {
-                    return;
-                }
-                j += 1;
+    //Rallies onto the entity under the click if there is one
+    pub fn set_rally<'f>(&'f mut self, point: Point, entity: Option<u32>) -> bool {
+        if !self.selected.is_building() {
+            return false;
+        }
+
+        let building = &mut self.buildings[self.selected.index()];
+        building.rally = match entity {
+            Some(id) if id != building.id => Some(Rally::Entity { id, last_seen: point }),
+            _ => Some(Rally::Point(point)),
+        };
+        true
+    }
+
+    //Current location of an entity rally, only this player's entities are tracked
+    fn get_rally_point<'f>(&'f self, rally: Rally) -> Point {
+        match rally {
+            Rally::Entity { id, .. } => {
+                self.units.iter()
+                    .find(|unit| unit.id == id)
+                    .map(|unit| unit.get_location())
+                    .or(self.buildings.iter()
+                        .find(|building| building.id == id)
+                        .map(|building| building.collider.center()))
+                    .unwrap_or(rally.get_point())
+            },
+            Rally::Point(point) => { point }
+        }
+    }
+
+    //Remembers where rallied onto entities are, so the rally stays there once they're gone
+    pub fn update_rallies<'f>(&'f mut self) {
+        let mut i: usize = 0;
+        while i < self.buildings.len() {
+            if let Some(rally @ Rally::Entity { id, .. }) = self.buildings[i].rally {
+                let last_seen = self.get_rally_point(rally);
+                self.buildings[i].rally = Some(Rally::Entity { id, last_seen });
             }
             i += 1;
         }
-        
-        i = 0;
-        while i < w_cells {
-            let mut j: i32 = 0;
-            while j < h_cells {
-                let ii = (cell_x + i) as usize;
-                let jj = (cell_y + j) as usize;
-                game_map.grid[ii][jj].occupy();
+    }
+
+    //Ground units standing in the way of a building, its own worker never is
+    fn placement_blockers<'f>(&'f self, index: usize, targets: &'f [Target]) -> Vec<Rect> {
+        let site = self.buildings[index].id;
+        let builder = self.units.iter()
+            .find(|unit| unit.construct == Some(site))
+            .map(|unit| unit.id)
+            .or(self.builder);
+
+        targets.iter()
+            .filter(|target| !target.collider_type.is_air() && Some(target.id) != builder)
+            .map(|target| target.collider)
+            .collect()
+    }
+
+    //Refineries go on a free geyser, everything else needs buildable cells
+    //that nothing is standing on
+    pub fn can_place<'f>(&'f self, index: usize, game_map: &'f World, targets: &'f [Target])
+            -> Result<(), PlacementError> {
+        let building = &self.buildings[index];
+
+        if building.building_type.requires_geyser() {
+            return match game_map.free_geyser_at(building.collider) {
+                Some(..) => Ok(()),
+                None => Err(PlacementError::NeedsGeyser),
+            };
+        }
+
+        game_map.can_place(building.collider, &self.placement_blockers(index, targets))
+    }
+
+    //Follows the mouse with the ghost, tinting the cells it would cover
+    pub fn move_ghost<'f>(&'f mut self, mouse_point: Point, cam_viewport: Rect,
+            game_map: &'f mut World, targets: &'f [Target]) {
+        if !self.placing_building {
+            return;
+        }
+
+        self.dehighlight(game_map);
+        let index = self.selected.index();
+        self.buildings[index].move_building(mouse_point, cam_viewport, &game_map.grid);
+        self.buildings[index].snap_to_geyser(game_map);
+
+        let blockers = self.placement_blockers(index, targets);
+        self.buildings[index].highlight_cells(game_map, &blockers);
+    }
+
+    //Pays for the ghost and leaves it as a site, its worker walks over to build it
+    pub fn place_building<'f>(&'f mut self, game_map: &'f mut World, targets: &'f [Target]) {
+        let index = self.selected.index();
+        if let Err(err) = self.can_place(index, game_map, targets) {
+            self.notify(err.to_string());
+            return;
+        }
 
-                j += 1;
+        //Landing costs nothing, the ghost only marks where its building flies to
+        if let Some(lander) = self.lander.take() {
+            self.dehighlight(game_map);
+            let site = self.buildings[index].collider.top_left();
+            self.remove_building(index);
+            if let Some(i) = self.buildings.iter().position(|building| building.id == lander) {
+                self.buildings[i].land_at(site);
+                self.selected = Selection::Building(i);
             }
+            return;
+        }
 
-            i += 1;
+        if let Err(err) = self.resources.spend(self.buildings[index].building_type.get_cost()) {
+            self.notify(err.to_string());
+            return;
         }
-          
-        self.buildings[index].status = BuildingStatus::Built;
+
+        self.dehighlight(game_map);
+        self.buildings[index].status = BuildingStatus::NotBuilt;
+        let site = self.buildings[index].id;
+        let builder = self.builder.take();
         self.deselect();
 
+        //A builder that died in the meantime leaves the site to be refunded
+        if let Some(i) = self.units.iter().position(|unit| Some(unit.id) == builder) {
+            self.units[i].build(site);
+            self.selected = Selection::Unit(i);
+        }
     }
 
-    pub fn start_placing_building<'f>(&'f mut self, action_type: ButtonFunction) {
-        let building_type: BuildingType;
-        match action_type {
-            ButtonFunction::PlaceBarracks => {
-                building_type = BuildingType::Barracks;
-            },
-            ButtonFunction::PlaceCommandCentre => {
-                building_type = BuildingType::CommandCentre;
+    //Puts down an already finished building, like the starting Command Centre
+    pub fn spawn_building<'f>(&'f mut self, mut building: Building, game_map: &'f mut World) {
+        game_map.set_occupied(building.collider, true);
+        building.status = BuildingStatus::Built;
+        self.buildings.push(building);
+    }
+
+    //Hands the player a ghost to place, built by the first worker in the selection
+    pub fn start_placing_building<'f>(&'f mut self, action_type: ButtonFunction,
+            tx_mgr: &'f TextureManager) {
+        let building_type = match action_type {
+            ButtonFunction::PlaceBarracks => { BuildingType::Barracks },
+            ButtonFunction::PlaceCommandCentre => { BuildingType::CommandCentre },
+            ButtonFunction::PlaceRefinery => { BuildingType::Refinery },
+            ButtonFunction::PlaceSupplyDepot => { BuildingType::SupplyDepot },
+            _ => { 
+                println!("------Error when selecting building type for placement!!");
+                return;
+            }
+        };
+
+        if let Err(err) = self.resources.can_afford(building_type.get_cost()) {
+            self.notify(err.to_string());
+            return;
+        }
+
+        let builder = match self.get_commanded_units().into_iter()
+                .find(|i| self.units[*i].unit_type.can_build()) {
+            Some(i) => &self.units[i],
+            None => { return; }
+        };
+        let mut ghost = Building::new(builder.get_location(), building_type, self.faction,
+            builder.team, self.bottom_right_ui.to_owned(), tx_mgr);
+        ghost.status = BuildingStatus::Placing;
+        let builder = builder.id;
+
+        self.deselect();
+        self.buildings.push(ghost);
+        self.selected = Selection::Building(self.buildings.len() - 1);
+        self.builder = Some(builder);
+        self.placing_building = true;
+    }
+
+    //Throws away the ghost being placed, nothing was paid for it yet
+    pub fn cancel_placement<'f>(&'f mut self, game_map: &'f mut World) {
+        if !self.placing_building {
+            return;
+        }
+
+        self.dehighlight(game_map);
+        let index = self.selected.index();
+        self.remove_building(index);
+        self.builder = None;
+
+        //A flying building goes back to being the selection
+        if let Some(lander) = self.lander.take() {
+            if let Some(i) = self.buildings.iter().position(|building| building.id == lander) {
+                self.selected = Selection::Building(i);
+            }
+        }
+    }
+
+    //Takes a finished building into the air, it has to finish training first
+    pub fn lift_off<'f>(&'f mut self, index: usize, game_map: &'f mut World) {
+        let building = &mut self.buildings[index];
+        if building.status != BuildingStatus::Built || !building.building_type.can_lift() {
+            return;
+        }
+        if !building.production_queue.is_empty() {
+            self.notify("Can't lift off while training".to_string());
+            return;
+        }
+
+        building.lift_off(game_map);
+    }
+
+    //Hands the player a ghost of a flying building to pick where it lands
+    pub fn start_landing<'f>(&'f mut self, index: usize, tx_mgr: &'f TextureManager) {
+        let flyer = &self.buildings[index];
+        if !flyer.is_airborne() {
+            return;
+        }
+
+        let mut ghost = Building::new(flyer.collider.top_left(), flyer.building_type,
+            self.faction, flyer.team, self.bottom_right_ui.to_owned(), tx_mgr);
+        ghost.status = BuildingStatus::Placing;
+        let lander = flyer.id;
+
+        self.deselect();
+        self.buildings.push(ghost);
+        self.selected = Selection::Building(self.buildings.len() - 1);
+        self.lander = Some(lander);
+        self.placing_building = true;
+    }
+
+    //Sends the selected building somewhere if it's flying, false if it isn't
+    pub fn command_fly<'f>(&'f mut self, point: Point) -> bool {
+        match self.selected {
+            Selection::Building(i) if self.buildings[i].is_airborne() => {
+                self.buildings[i].fly_to(point);
+                true
             },
-            ButtonFunction::Back => {
-                self.deselect();
+            _ => { false }
+        }
+    }
+
+    //Moves flying buildings and puts down the ones that reached their site. Units
+    //under the site keep it waiting, a site that got built on leaves it hovering
+    pub fn update_flying<'f>(&'f mut self, game_map: &'f mut World, targets: &'f [Target]) {
+        let mut i: usize = 0;
+        while i < self.buildings.len() {
+            let arriving = self.buildings[i].move_target.is_some();
+            if self.buildings[i].update_flight() {
+                match self.can_place(i, game_map, targets) {
+                    Ok(()) => { self.buildings[i].land(game_map); },
+                    Err(PlacementError::BlockedByUnit) => {
+                        if arriving {
+                            self.notify(PlacementError::BlockedByUnit.to_string());
+                        }
+                    },
+                    Err(err) => {
+                        self.notify(err.to_string());
+                        self.buildings[i].stop();
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
+    //Tears down an unfinished building. Sites nobody started on are refunded in full,
+    //ones under construction give back CANCEL_REFUND_PERCENT of their cost
+    pub fn cancel_building<'f>(&'f mut self, index: usize, game_map: &'f mut World) {
+        let cost = self.buildings[index].building_type.get_cost();
+        let refund = match self.buildings[index].status {
+            BuildingStatus::Placing => {
+                self.cancel_placement(game_map);
                 return;
             },
-            _ => { 
-                println!("------Error when selecting building type for placement!!");
-                self.deselect();
+            BuildingStatus::NotBuilt => { cost },
+            BuildingStatus::UnderConstruction { .. } => {
+                cost.percent(general::CANCEL_REFUND_PERCENT)
+            },
+            BuildingStatus::Built | BuildingStatus::Flying | BuildingStatus::Landing => {
                 return;
             }
+        };
+
+        //Its worker is let go by update_construction once the site is gone
+        self.buildings[index].release_footprint(game_map);
+        self.remove_building(index);
+        self.resources.refund(refund);
+    }
+
+    //Removes a building that never got finished, keeping the selection pointed at
+    //the same buildings
+    fn remove_building<'f>(&'f mut self, index: usize) -> Building {
+        match self.selected {
+            Selection::Building(i) if i == index => { self.deselect(); },
+            Selection::Building(i) if i > index => { self.selected = Selection::Building(i - 1); },
+            _ => {}
+        }
+        self.buildings.remove(index)
+    }
+
+    //Sends the first commanded worker to carry on with an unfinished building
+    pub fn order_construct<'f>(&'f mut self, id: u32) -> bool {
+        if !self.buildings.iter().any(|building| building.id == id &&
+                matches!(building.status, BuildingStatus::UnderConstruction { .. })) {
+            return false;
         }
 
-        {
-            let mut i: usize = 0;
-            while i < self.buildings.len() {
-                if self.buildings[i].status == BuildingStatus::NotBuilt {
-                    if self.buildings[i].building_type == building_type {
-                        self.deselect();
-                        self.selected = Selection::Building(i);
-                        self.buildings[i].status = BuildingStatus::Placing;
-                        self.placing_building = true;
-                        break;
+        match self.get_commanded_units().into_iter()
+                .find(|i| self.units[*i].unit_type.can_build()) {
+            Some(i) => {
+                self.units[i].build(id);
+                true
+            },
+            None => { false }
+        }
+    }
+
+    //Walks workers to their sites, lays the foundation once they get there and
+    //advances construction while they stay. Construction pauses without a worker
+    //on site, a site nobody is coming to build any more is refunded.
+    pub fn update_construction<'f>(&'f mut self, game_map: &'f mut World,
+            targets: &'f [Target]) {
+        //Sites that got destroyed leave their workers without an order
+        let sites: Vec<u32> = self.buildings.iter().map(|building| building.id).collect();
+        self.units.iter_mut()
+            .filter(|unit| unit.construct.is_some_and(|site| !sites.contains(&site)))
+            .for_each(|unit| unit.construct = None);
+
+        let mut i: usize = 0;
+        while i < self.buildings.len() {
+            let site = self.buildings[i].id;
+            let collider = self.buildings[i].collider;
+            let builder = self.units.iter().position(|unit| unit.construct == Some(site));
+
+            let mut arrived = false;
+            let on_site = match builder {
+                Some(b) if combat::edge_distance(self.units[b].collider, collider) <=
+                        building::BUILD_RANGE => {
+                    if self.units[b].is_moving() {
+                        self.units[b].arrive();
+                        arrived = true;
                     }
-                }       
+                    self.units[b].destination = None;
+                    true
+                },
+                Some(b) => {
+                    let unit = &mut self.units[b];
+                    if unit.destination != Some(collider.center()) || !unit.is_moving() {
+                        unit.move_to(collider.center(), &*game_map);
+                    }
+                    false
+                },
+                None => { false }
+            };
 
-                i += 1;
+            match self.buildings[i].status {
+                BuildingStatus::NotBuilt if builder.is_none() => {
+                    let building = self.remove_building(i);
+                    self.resources.refund(building.building_type.get_cost());
+                    continue;
+                },
+                BuildingStatus::NotBuilt if on_site => {
+                    //Units that wandered onto the site since keep the worker waiting,
+                    //anything else in the way gets the site refunded
+                    match self.can_place(i, game_map, targets) {
+                        Ok(()) => {},
+                        Err(PlacementError::BlockedByUnit) => {
+                            if arrived {
+                                self.notify(PlacementError::BlockedByUnit.to_string());
+                            }
+                            i += 1;
+                            continue;
+                        },
+                        Err(err) => {
+                            self.notify(err.to_string());
+                            if let Some(b) = builder {
+                                self.units[b].stop();
+                            }
+                            let building = self.remove_building(i);
+                            self.resources.refund(building.building_type.get_cost());
+                            continue;
+                        }
+                    }
+
+                    match game_map.free_geyser_at(collider) {
+                        //The geyser already blocks the cells under the refinery
+                        Some(id) if self.buildings[i].building_type.requires_geyser() => {
+                            game_map.set_extractor(id, Some(site));
+                        },
+                        _ => { game_map.set_occupied(collider, true); }
+                    }
+                    self.buildings[i].begin_construction();
+                },
+                BuildingStatus::UnderConstruction { .. } => {
+                    let finished = self.buildings[i].update_build(on_site);
+                    if let (true, Some(b)) = (finished, builder) {
+                        self.units[b].construct = None;
+                    }
+                },
+                _ => {}
             }
+            i += 1;
         }
     }
     
-    pub fn check_place_construction_flag<'f>(&'f self) -> bool {
-        for building in self.buildings.iter() {
-            if building.place_construction_flag {
+    //Cancels the clicked entry of the selected building's production queue
+    pub fn check_queue_click<'f>(&'f mut self, point: Point) -> bool {
+        if !self.selected.is_building() {
+            return false;
+        }
+
+        let building = &mut self.buildings[self.selected.index()];
+        let mut i: usize = 0;
+        while i < building.production_queue.len() {
+            if self.queue_ui[i].collider.contains_point(point) {
+                if let Some(cancelled) = building.cancel_construction(i) {
+                    self.resources.refund(cancelled.product.get_button_function().get_cost());
+                }
                 return true;
             }
+            i += 1;
         }
+
         false
     }
-    
+
+    //Unit Interactions
+
+    pub fn select_units_in<'f>(&'f mut self, area: Rect) -> bool {
+        self.deselect();
+        self.selected_units = (0..self.units.len())
+            .filter(|i| area.has_intersection(self.units[*i].collider))
+            .collect();
+
+        match self.selected_units.first() {
+            Some(first) => {
+                self.selected = Selection::Unit(*first);
+                true
+            },
+            None => { false }
+        }
+    }
+
+    fn get_commanded_units<'f>(&'f self) -> Vec<usize> {
+        if !self.selected_units.is_empty() {
+            self.selected_units.to_owned()
+        } else if self.selected.is_unit() {
+            vec![self.selected.index()]
+        } else {
+            vec![]
+        }
+    }
+
+    //Big groups share one flow field, smaller ones path with A* per unit.
+    //Flyers always head straight for the target.
+    pub fn command_move<'f>(&'f mut self, target: Point, game_map: &'f World) -> bool {
+        let commanded = self.get_commanded_units();
+        if commanded.is_empty() {
+            return false;
+        }
+        commanded.iter().for_each(|i| {
+            self.units[*i].attack_target = None;
+            self.units[*i].caster.pending = None;
+            self.units[*i].gather = None;
+            self.units[*i].construct = None;
+        });
+
+        let (flyers, walkers): (Vec<usize>, Vec<usize>) = commanded.into_iter()
+            .partition(|i| self.units[*i].is_air());
+        flyers.iter().for_each(|i| self.units[*i].move_to(target, game_map));
+
+        if walkers.len() >= FLOW_FIELD_GROUP_SIZE {
+            let footprint = walkers.iter()
+                .map(|i| self.units[*i].footprint())
+                .max().unwrap();
+            let starts: Vec<(usize, usize)> = walkers.iter()
+                .map(|i| self.units[*i].footprint_cell(self.units[*i].get_location(), game_map))
+                .collect();
+            let goal = self.units[walkers[0]].footprint_cell(target, game_map);
+
+            let id = self.next_flow_field_id;
+            self.next_flow_field_id += 1;
+            self.flow_fields.insert(id, FlowField::new(game_map, goal, footprint, &starts));
+
+            //Units fan out around the target instead of all aiming for one pixel
+            let arrive_radius = (walkers.len() as f32).sqrt() * 15.0;
+            walkers.iter().for_each(|i|
+                self.units[*i].follow_flow_field(id, target, arrive_radius));
+        } else {
+            walkers.iter().for_each(|i| self.units[*i].move_to(target, game_map));
+        }
+
+        true
+    }
+
+    pub fn command_attack<'f>(&'f mut self, id: u32) -> bool {
+        let commanded: Vec<usize> = self.get_commanded_units().into_iter()
+            .filter(|i| self.units[*i].weapon.is_some())
+            .collect();
+
+        commanded.iter().for_each(|i| self.units[*i].attack(id));
+        !commanded.is_empty()
+    }
+
+    //Sends the commanded workers to mine the field, false if none of them can gather
+    pub fn order_gather<'f>(&'f mut self, id: u32, game_map: &'f World) -> bool {
+        let field = match game_map.resource_field(id) {
+            Some(field) => *field,
+            None => { return false; }
+        };
+        let gatherers: Vec<usize> = self.get_commanded_units().into_iter()
+            .filter(|i| self.units[*i].unit_type.can_gather())
+            .collect();
+        if gatherers.is_empty() {
+            return false;
+        }
+
+        if !self.can_harvest(&field) {
+            self.notify("Requires a refinery".to_string());
+            return true;
+        }
+
+        gatherers.iter().for_each(|i| self.units[*i].gather_from(&field));
+        true
+    }
+
+    //Units alive plus every unit waiting in a production queue
+    pub fn get_supply_used<'f>(&'f self) -> i32 {
+        let queued: i32 = self.buildings.iter()
+            .flat_map(|building| building.production_queue.iter())
+            .map(|construction| match construction.product {
+                ConstructionType::Unit(unit_type) => { unit_type.get_supply() },
+            })
+            .sum();
+
+        self.units.iter().map(|unit| unit.unit_type.get_supply()).sum::<i32>() + queued
+    }
+
+    pub fn get_supply_cap<'f>(&'f self) -> i32 {
+        self.buildings.iter()
+            .filter(|building| building.is_finished())
+            .map(|building| building.building_type.get_supply_provided())
+            .sum::<i32>()
+            .min(general::MAX_SUPPLY)
+    }
+
+    //Whether there's room for something that takes up supply
+    pub fn check_supply<'f>(&'f self, supply: i32) -> Result<(), ResourceError> {
+        if supply > 0 && self.get_supply_used() + supply > self.get_supply_cap() {
+            return Err(ResourceError::SupplyBlocked);
+        }
+        Ok(())
+    }
+
+    //Minerals are free for all, gas only flows through this player's own refinery
+    fn can_harvest<'f>(&'f self, field: &'f ResourceField) -> bool {
+        match field.kind {
+            ResourceKind::Minerals => { true },
+            ResourceKind::Gas => {
+                match field.extractor {
+                    Some(id) => { self.get_extractors().contains(&id) },
+                    None => { false }
+                }
+            }
+        }
+    }
+
+    //Ids of the finished refineries this player owns
+    fn get_extractors<'f>(&'f self) -> Vec<u32> {
+        self.buildings.iter()
+            .filter(|building| building.status == BuildingStatus::Built &&
+                building.building_type.requires_geyser())
+            .map(|building| building.id)
+            .collect()
+    }
+
+    //Runs the gather loop of every worker, whatever they drop off is banked
+    pub fn update_gathering<'f>(&'f mut self, game_map: &'f mut World) {
+        let depots: Vec<Rect> = self.buildings.iter()
+            .filter(|building| building.status == BuildingStatus::Built &&
+                building.building_type.is_resource_depot())
+            .map(|building| building.collider)
+            .collect();
+        let extractors = self.get_extractors();
+
+        for unit in self.units.iter_mut() {
+            if let Some((kind, amount)) = gather::step(unit, &depots, &extractors, game_map) {
+                self.resources.deposit(kind, amount);
+            }
+        }
+    }
+
+    //Instant abilities go off right away, the rest wait for a target click
+    pub fn use_ability<'f>(&'f mut self, ability: AbilityKind) {
+        match ability.get_targeting() {
+            Targeting::Instant => { self.queue_cast(ability, CastTarget::None); },
+            _ => { self.targeting = Some(ability); }
+        }
+    }
+
+    //Finishes targeting with a click on point, entity is whatever was under it
+    pub fn cast_at<'f>(&'f mut self, point: Point, entity: Option<u32>,
+            game_map: &'f World) -> bool {
+        let ability = match self.targeting.take() {
+            Some(ability) => ability,
+            None => { return false; }
+        };
+
+        let target = match ability.get_targeting() {
+            Targeting::Unit => {
+                match entity {
+                    Some(id) => CastTarget::Entity(id),
+                    None => {
+                        self.notify(format!("Can't cast: {}", CastError::InvalidTarget));
+                        return true;
+                    }
+                }
+            },
+            Targeting::Point | Targeting::Area(..) => { CastTarget::Point(point) },
+            Targeting::Instant => { CastTarget::None },
+        };
+
+        match (ability, target) {
+            (AbilityKind::Move, CastTarget::Point(point)) => {
+                self.command_move(point, game_map);
+            },
+            (AbilityKind::Attack, CastTarget::Entity(id)) => {
+                self.command_attack(id);
+            },
+            _ => { self.queue_cast(ability, target); }
+        }
+
+        true
+    }
+
+    //The selected building, or the first commanded unit that's ready, gets the cast
+    fn queue_cast<'f>(&'f mut self, ability: AbilityKind, target: CastTarget) {
+        let cast = Cast { ability, target };
+
+        if self.selected.is_building() {
+            let building = &mut self.buildings[self.selected.index()];
+            if building.has_ability(ability) {
+                match building.caster.can_cast(ability) {
+                    Ok(()) => { building.caster.pending = Some(cast); },
+                    Err(err) => { self.notify(format!("Can't cast: {}", err)); }
+                }
+            }
+            return;
+        }
+
+        let casters: Vec<usize> = self.get_commanded_units().into_iter()
+            .filter(|i| self.units[*i].has_ability(ability))
+            .collect();
+        let ready = casters.iter()
+            .find(|i| self.units[**i].caster.can_cast(ability).is_ok());
+
+        match (ready, casters.first()) {
+            (Some(i), _) => {
+                self.units[*i].attack_target = None;
+                self.units[*i].caster.pending = Some(cast);
+            },
+            (None, Some(i)) => {
+                if let Err(err) = self.units[*i].caster.can_cast(ability) {
+                    self.notify(format!("Can't cast: {}", err));
+                }
+            },
+            (None, None) => {}
+        }
+    }
+
+    //Regenerates energy and resolves the pending casts that got in range
+    pub fn update_casts<'f>(&'f mut self, targets: &'f [Target], game_map: &'f World,
+            projectiles: &'f mut Vec<Projectile>) {
+        self.units.iter_mut().for_each(|unit| unit.caster.regenerate());
+        self.buildings.iter_mut().for_each(|building| building.caster.regenerate());
+
+        let mut casts: Vec<(Cast, u32, Rect, i32)> = vec![];
+        let mut failed: Option<CastError> = None;
+        for unit in self.units.iter_mut() {
+            match unit.update_cast(targets, game_map) {
+                Some(Ok(cast)) => { casts.push((cast, unit.id, unit.collider, unit.team)); },
+                Some(Err(err)) => { failed = Some(err); },
+                None => {}
+            }
+        }
+        for building in self.buildings.iter_mut() {
+            match building.update_cast(targets) {
+                Some(Ok(cast)) => {
+                    casts.push((cast, building.id, building.collider, building.team));
+                },
+                Some(Err(err)) => { failed = Some(err); },
+                None => {}
+            }
+        }
+        if let Some(err) = failed {
+            self.notify(format!("Can't cast: {}", err));
+        }
+
+        for (cast, caster_id, caster, team) in casts {
+            match (cast.ability, cast.target) {
+                (AbilityKind::Bombard, CastTarget::Point(point)) => {
+                    let radius = match cast.ability.get_targeting() {
+                        Targeting::Area(radius) => radius as f32,
+                        _ => { 0.0 }
+                    };
+                    projectiles.push(Projectile::at_point(caster.center(), point, caster_id, team,
+                        ability::BOMBARD_DAMAGE, WeaponTargets::Both,
+                        ProjectileStats::new(8.0, false, radius, true)));
+                },
+                (AbilityKind::Nanorepair, _) => {
+                    self.units.iter_mut()
+                        .filter(|unit| combat::edge_distance(caster, unit.collider) <=
+                            ability::NANOREPAIR_RANGE)
+                        .for_each(|unit| unit.hp =
+                            (unit.hp + ability::NANOREPAIR_HEAL).min(unit.max_hp));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    pub fn update_attacks<'f>(&'f mut self, targets: &'f [Target], game_map: &'f World,
+            shots: &'f mut Vec<Shot>) {
+        self.units.iter_mut()
+            .filter_map(|unit| unit.update_attack(targets, game_map))
+            .for_each(|shot| shots.push(shot));
+        self.buildings.iter_mut()
+            .filter_map(|building| building.update_attack(targets))
+            .for_each(|shot| shots.push(shot));
+    }
+
+    pub fn take_hits<'f>(&'f mut self, hits: &'f [Hit]) {
+        for hit in hits.iter() {
+            if let Some(unit) = self.units.iter_mut().find(|unit| unit.id == hit.id) {
+                unit.hp -= combat::damage_after_armor(hit.damage, unit.armor);
+            } else if let Some(building) = self.buildings.iter_mut()
+                    .find(|building| building.id == hit.id) {
+                building.hp -= combat::damage_after_armor(hit.damage, building.armor);
+            }
+        }
+    }
+
+    //Removes units and buildings at 0 hp, freeing the cells of dead buildings
+    //and fixing up the indices held by the selection
+    pub fn remove_dead<'f>(&'f mut self, game_map: &'f mut World) {
+        let unit_indices = new_indices(self.units.iter().map(|unit| unit.hp > 0).collect());
+        let building_indices = new_indices(self.buildings.iter()
+            .map(|building| building.hp > 0).collect());
+
+        self.buildings.iter()
+            .filter(|building| building.hp <= 0)
+            .for_each(|building| building.release_footprint(game_map));
+
+        self.units.retain(|unit| unit.hp > 0);
+        self.buildings.retain(|building| building.hp > 0);
+
+        self.selected_units = self.selected_units.iter()
+            .filter_map(|i| unit_indices[*i])
+            .collect();
+        let selected = match self.selected {
+            Selection::Unit(index) => {
+                unit_indices[index].or(self.selected_units.first().copied())
+                    .map(Selection::Unit)
+            },
+            Selection::Building(index) => { building_indices[index].map(Selection::Building) },
+            Selection::None => { Some(Selection::None) }
+        };
+
+        match selected {
+            Some(selected) => { self.selected = selected; },
+            None => {
+                self.selected = Selection::None;
+                self.placing_building = false;
+            }
+        }
+    }
+
+    pub fn update_units<'f>(&'f mut self, game_map: &'f World) {
+        //Drop fields nobody follows, rebuild the ones whose region changed
+        let units = &self.units;
+        self.flow_fields.retain(|id, _| units.iter().any(|unit| unit.flow_field == Some(*id)));
+        self.flow_fields.values_mut().for_each(|field| {
+            field.mark_dirty_if_affected(&game_map.occupancy_changes);
+            if field.dirty {
+                field.rebuild(game_map);
+            }
+        });
+
+        let flow_fields = &self.flow_fields;
+        self.units.iter_mut().for_each(|unit| unit.update(flow_fields, game_map));
+    }
+
+    //Local steering, keeps units from stacking on each other or on buildings. bodies
+    //holds every player's units, this player's start at first_body
+    pub fn steer_units<'f>(&'f mut self, bodies: &'f [Body], first_body: usize,
+            game_map: &'f World) {
+        let mut i: usize = 0;
+        while i < self.units.len() {
+            if steering::blocked_by_arrived(bodies, first_body + i) {
+                self.units[i].arrive();
+            }
+
+            let offset = steering::steer(bodies, first_body + i, self.units[i].speed);
+            self.units[i].nudge(offset, game_map);
+            i += 1;
+        }
+    }
+
+    //Outlines the selected units, drawn in world space
+    pub fn render_selection<'f>(&'f self, canvas: &'f mut WindowCanvas) {
+        let previous_color = canvas.draw_color();
+        canvas.set_draw_color(Color::RGBA(0, 255, 0, 255));
+
+        self.get_commanded_units().iter().for_each(|i| {
+            let _ = canvas.draw_rect(self.units[*i].collider);
+        });
+
+        //Rally line and flag of the selected building
+        if self.selected.is_building() {
+            let building = &self.buildings[self.selected.index()];
+            if let Some(rally) = building.rally {
+                let flag = self.get_rally_point(rally);
+                let _ = canvas.draw_line(building.collider.center(), flag);
+                let _ = canvas.draw_line(flag, flag.offset(0, -20));
+                let _ = canvas.fill_rect(Rect::new(flag.x, flag.y - 20, 12, 8));
+            }
+        }
+
+        canvas.set_draw_color(previous_color);
+    }
+
+    //Team coloured health bar above every unit and above buildings that are selected or
+    //damaged, with the construction progress over unfinished ones. Drawn in world space.
+    pub fn render_health_bars<'f>(&'f self, canvas: &'f mut WindowCanvas) {
+        let previous_color = canvas.draw_color();
+
+        let shown_buildings: Vec<usize> = (0..self.buildings.len())
+            .filter(|i| (self.buildings[*i].on_grid() || self.buildings[*i].is_airborne()) &&
+                (self.selected == Selection::Building(*i) ||
+                self.buildings[*i].hp < self.buildings[*i].max_hp))
+            .collect();
+
+        let bars = self.units.iter()
+            .map(|unit| (unit.team, unit.collider, unit.hp, unit.max_hp))
+            .chain(shown_buildings.iter()
+                .map(|i| &self.buildings[*i])
+                .map(|building| (building.team, building.collider, building.hp,
+                    building.max_hp)));
+
+        for (team, collider, hp, max_hp) in bars {
+            let bar = Rect::new(collider.x, collider.y - 6, collider.width(), 4);
+            let filled = (bar.width() as i32 * hp.max(0) / max_hp.max(1)).max(1) as u32;
+
+            canvas.set_draw_color(Color::RGBA(30, 30, 30, 255));
+            let _ = canvas.fill_rect(bar);
+            canvas.set_draw_color(general::team_color(team));
+            let _ = canvas.fill_rect(Rect::new(bar.x, bar.y, filled, bar.height()));
+        }
+
+        for i in shown_buildings.iter() {
+            let building = &self.buildings[*i];
+            if let Some(progress) = building.construction_progress() {
+                render_progress_bar(Rect::new(building.collider.x, building.collider.y - 12,
+                    building.collider.width(), 4), progress, canvas);
+            }
+        }
+
+        //Energy, just under the health bar
+        let casters = self.units.iter()
+            .map(|unit| (unit.collider, &unit.caster))
+            .chain(self.buildings.iter()
+                .filter(|building| building.is_finished())
+                .map(|building| (building.collider, &building.caster)))
+            .filter(|(_, caster)| caster.max_energy > 0.0);
+
+        for (collider, caster) in casters {
+            let filled = (collider.width() as f32 * caster.energy / caster.max_energy) as u32;
+            canvas.set_draw_color(Color::RGBA(30, 30, 30, 255));
+            let _ = canvas.fill_rect(Rect::new(collider.x, collider.y - 2, collider.width(), 2));
+            canvas.set_draw_color(Color::RGBA(150, 60, 230, 255));
+            let _ = canvas.fill_rect(Rect::new(collider.x, collider.y - 2, filled.max(1), 2));
+        }
+
+        canvas.set_draw_color(previous_color);
+    }
+
     //General
     fn get_buttons<'f>(&'f self) -> Option<&[Option<Button>; 16]> {
         let mut buttons: Option<&[Option<Button>; 16]> = None;
@@ -195,15 +1059,24 @@ impl Player {
     fn get_selectables<'f>(&'f self) -> Vec<Selection> {
         let mut selectables: Vec<Selection> = vec![];
         let mut i: usize = 0;
+        while i < self.units.len() { // Flyers are drawn over everything, so they go first
+            if self.units[i].is_air() {
+                selectables.push(self.units[i].get_selection(i));
+            }
+            i += 1;
+        }
+        i = 0;
+        while i < self.units.len() { // Then ground units, drawn over buildings
+            if !self.units[i].is_air() {
+                selectables.push(self.units[i].get_selection(i));
+            }
+            i += 1;
+        }
+        i = 0;
         while i < self.buildings.len() {
             selectables.push(self.buildings[i].get_selection(i));
             i += 1;
         }
-        /*i = 0;
-        while i < self.units.len() {
-            selectables.push(self.units[i].get_selection(i));
-            i += 1;
-        }*/
         selectables
     }
     
@@ -212,14 +1085,19 @@ impl Player {
             Selection::Building(index) => {
                 self.buildings[index].collider.contains_point(click)
             },
-            /*Selection::Unit(index) => {
+            Selection::Unit(index) => {
                 self.units[index].collider.contains_point(click)
-            },*/
+            },
             _ => { false }
         }
     }
     
     pub fn try_selecting<'f>(&'f mut self, click: Point) -> bool {
+        if self.placing_building { // The ghost stays selected until placed or cancelled
+            return false;
+        }
+        self.selected_units.clear();
+
         for selectable in self.get_selectables() {
             if self.check_selecting_click(selectable, click) {
                 self.selected = selectable;
@@ -238,53 +1116,142 @@ impl Player {
     }
 
     pub fn deselect<'f>(&'f mut self) {
+        self.selected_units.clear();
+        self.targeting = None;
+
         if self.selected.is_unit() {
+            let index = self.selected.index();
+            self.units[index].button_panel_index = 0;
             self.selected = Selection::None;
         }
 
         if self.selected.is_building() {
             let index = self.selected.index();
             self.buildings[index].button_panel_index = 0;
-            self.buildings[index].place_construction_flag = false;
             self.selected = Selection::None;
         }
 
         self.placing_building = false;
     }
     
-    pub fn check_button<'f>(&'f mut self, point: Point, index: usize) -> bool {
-        if self.check_place_construction_flag() {
-            if self.construction_buttons[index].is_some() {
-                if self.construction_buttons[index].unwrap().ui.collider.contains_point(point) {
-                    let temp_btn_fn = self.construction_buttons[index]
-                        .unwrap().to_owned().btn_function;
-                    self.start_placing_building(temp_btn_fn);
-                }
+    pub fn check_button<'f>(&'f mut self, point: Point, index: usize,
+            tx_mgr: &'f TextureManager, game_map: &'f mut World) -> bool {
+        let clicked = self.get_buttons()
+            .and_then(|buttons| buttons[index])
+            .is_some_and(|button| button.ui.collider.contains_point(point));
+
+        if clicked {
+            self.press_button(index, tx_mgr, game_map);
+        }
+        clicked
+    }
+
+    //Presses the button of the shown card that has this hotkey, false if there's none
+    pub fn check_hotkey<'f>(&'f mut self, keycode: Keycode, tx_mgr: &'f TextureManager,
+            game_map: &'f mut World) -> bool {
+        let index = self.get_buttons().and_then(|buttons| buttons.iter()
+            .position(|button| button.is_some_and(|button| button.hotkey == Some(keycode))));
+
+        match index {
+            Some(index) => {
+                self.press_button(index, tx_mgr, game_map);
+                true
+            },
+            None => { false }
+        }
+    }
+
+    fn press_button<'f>(&'f mut self, index: usize, tx_mgr: &'f TextureManager,
+            game_map: &'f mut World) {
+        let button = match self.get_buttons().and_then(|buttons| buttons[index]) {
+            Some(button) => button,
+            None => { return; }
+        };
+        let temp_btn_fnc = button.btn_function;
+
+        if let Some(panel) = button.opens {
+            match self.selected {
+                Selection::Building(i) => { self.buildings[i].set_button_panel(panel); },
+                Selection::Unit(i) => { self.units[i].set_button_panel(panel); },
+                Selection::None => {}
             }
-        } else if self.selected.is_building() {
-            let buttons = self.get_buttons().unwrap();
+        }
 
-            if buttons[index].is_some() {
-                if buttons[index].unwrap().ui.collider.contains_point(point) {
-                    let temp_player_clone = self.to_owned();
-                    let temp_btn_fnc = buttons[index].unwrap().btn_function.to_owned();
-                    let temp_building: &mut Building = &mut self.
-                        buildings[self.selected.index()];
-                    temp_building.execute_fn(temp_btn_fnc, temp_player_clone); 
-                    return true;
-                }  
+        if self.selected.is_building() {
+            match temp_btn_fnc {
+                ButtonFunction::Ability(ability) => {
+                    self.use_ability(ability);
+                    return;
+                },
+                ButtonFunction::CancelConstruction => {
+                    self.cancel_building(self.selected.index(), game_map);
+                    return;
+                },
+                ButtonFunction::LiftOff => {
+                    self.lift_off(self.selected.index(), game_map);
+                    return;
+                },
+                ButtonFunction::Land => {
+                    self.start_landing(self.selected.index(), tx_mgr);
+                    return;
+                },
+                _ => {}
+            }
+
+            let cost = temp_btn_fnc.get_cost();
+            if let Err(err) = self.resources.can_afford(cost)
+                    .and(self.check_supply(temp_btn_fnc.get_supply())) {
+                self.notify(err.to_string());
+                return;
+            }
+
+            let temp_player_clone = self.to_owned();
+            let temp_building: &mut Building = &mut self.
+                buildings[self.selected.index()];
+            if temp_building.execute_fn(temp_btn_fnc, temp_player_clone) {
+                let _ = self.resources.spend(cost);
+            }
+        } else if self.selected.is_unit() {
+            match temp_btn_fnc {
+                ButtonFunction::Ability(ability) => { self.use_ability(ability); },
+                ButtonFunction::PlaceBarracks | ButtonFunction::PlaceCommandCentre |
+                        ButtonFunction::PlaceRefinery |
+                        ButtonFunction::PlaceSupplyDepot => {
+                    self.start_placing_building(temp_btn_fnc, tx_mgr);
+                },
+                ButtonFunction::OpenPanel | ButtonFunction::Back => {
+                    //Only flip the card on screen, done above
+                },
+                _ => {
+                    self.get_commanded_units().iter()
+                        .for_each(|i| self.units[*i].execute_fn(temp_btn_fnc));
+                }
             }
         }
+    }
 
-        return false;
-    } 
+    //Replaces the message shown near the bottom of the screen
+    pub fn notify<'f>(&'f mut self, message: String) {
+        self.notification = Some(Notification::new(message));
+    }
 
+    //Buildings and ground units, flyers are drawn later by render_air
     pub fn render_owned<'f>(&'f self, tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
-        self.buildings.iter().for_each(|b| {
+        self.buildings.iter().filter(|b| !b.is_airborne()).for_each(|b| {
+            b.render(tx_mgr, canvas);
+        });
+
+        self.units.iter().filter(|u| !u.is_air()).for_each(|u| {
+            u.render(tx_mgr, canvas);
+        });
+    }
+
+    pub fn render_air<'f>(&'f self, tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
+        self.buildings.iter().filter(|b| b.is_airborne()).for_each(|b| {
             b.render(tx_mgr, canvas);
         });
 
-        self.units.iter().for_each(|u| {
+        self.units.iter().filter(|u| u.is_air()).for_each(|u| {
             u.render(tx_mgr, canvas);
         });
     }
@@ -294,17 +1261,72 @@ impl Player {
             ui.render(tx_mgr, canvas);
         });
 
-        if self.check_place_construction_flag() {
-            self.construction_buttons.iter()
-                .filter(|btn| btn.is_some())
-                .for_each(|btn| btn.unwrap().render(tx_mgr, canvas));
-        
-        } else if self.selected.is_some() {
-            let buttons = self.get_buttons().unwrap();
+        { // Resources, top right
+            let resources = format!("MINERALS {}   GAS {}   SUPPLY {}/{}",
+                self.resources.minerals, self.resources.gas,
+                self.get_supply_used(), self.get_supply_cap());
+            let width = text::text_width(resources.as_str(), 3) as i32;
+            text::render_text(resources.as_str(),
+                Point::new(canvas.viewport().width() as i32 - width - 20, 20), 3, tx_mgr, canvas);
+        }
+
+        if let Some(notification) = self.notification.as_ref()
+                .filter(|notification| !notification.expired()) {
+            notification.render(tx_mgr, canvas);
+        }
+
+        if let Some(buttons) = self.get_buttons() {
             buttons.iter()
                 .filter(|btn| btn.is_some())
                 .for_each(|btn| btn.unwrap().render(tx_mgr, canvas));
         }
+
+        if self.selected.is_building() {
+            let building = &self.buildings[self.selected.index()];
+
+            //Progress of the building itself, or of the front of its queue
+            let progress = building.construction_progress()
+                .or(building.production_queue.front().map(|construction| construction.progress()));
+            if let Some(progress) = progress {
+                let panel = self.bottom_right_ui[0].collider;
+                render_progress_bar(Rect::new(panel.x + 6, panel.y - 5,
+                    general::MAX_QUEUE_LENGTH as u32 * 54 - 4, 4), progress, canvas);
+            }
+
+            let mut i: usize = 0;
+            while i < building.production_queue.len() {
+                self.queue_ui[i].render(tx_mgr, canvas);
+                UiElement::new(Sprite::new(self.queue_ui[i].collider,
+                    TextureType::UI { type_index: 0 }, tx_mgr), None,
+                    building.production_queue[i].product.get_button_function()
+                        .get_texture_index())
+                    .render(tx_mgr, canvas);
+                i += 1;
+            }
+        }
     }
 }
 
+fn render_progress_bar<'f>(bar: Rect, progress: f32, canvas: &'f mut WindowCanvas) {
+    let previous_color = canvas.draw_color();
+    let filled = ((bar.width() as f32 * progress.clamp(0.0, 1.0)) as u32).max(1);
+
+    canvas.set_draw_color(Color::RGBA(30, 30, 30, 255));
+    let _ = canvas.fill_rect(bar);
+    canvas.set_draw_color(Color::RGBA(230, 200, 60, 255));
+    let _ = canvas.fill_rect(Rect::new(bar.x, bar.y, filled, bar.height()));
+    canvas.set_draw_color(previous_color);
+}
+
+//Maps old indices to positions after dropping every entry that isn't kept
+fn new_indices(kept: Vec<bool>) -> Vec<Option<usize>> {
+    let mut next: usize = 0;
+    kept.iter().map(|keep| {
+        if *keep {
+            next += 1;
+            Some(next - 1)
+        } else {
+            None
+        }
+    }).collect()
+}
@@ -0,0 +1,202 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+
+use crate::combat::{self, Hit, Shot, Target, WeaponTargets};
+use crate::general::Renderable;
+use crate::sprite::TextureManager;
+
+const PROJECTILE_SIZE: u32 = 6;
+
+//How a weapon's projectiles fly and what they hit on impact
+#[derive(Clone, Copy)]
+pub struct ProjectileStats {
+    pub speed: f32, // Pixels per frame
+    pub homing: bool,
+    pub splash_radius: f32, // 0 only damages the entity at the impact point
+    pub friendly_fire: bool, // Also damages the shooter's own team
+}
+
+impl ProjectileStats {
+    pub fn new(speed: f32, homing: bool, splash_radius: f32, friendly_fire: bool)
+            -> ProjectileStats {
+        ProjectileStats { speed, homing, splash_radius, friendly_fire }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Projectile {
+    pub shooter: u32, // Never hit by its own projectile
+    pub team: i32,
+    pub position: (f32, f32),
+    pub velocity: (f32, f32),
+    pub target_point: (f32, f32),
+    pub target_id: Option<u32>, // Followed while alive if homing
+    pub damage: i32,
+    pub targets: WeaponTargets, // Splash only hurts what the weapon could have targeted
+    pub stats: ProjectileStats,
+    pub done: bool,
+}
+
+impl Projectile {
+    pub fn new<'f>(shot: &'f Shot, stats: ProjectileStats) -> Projectile {
+        let mut new_projectile = Projectile::at_point(shot.origin, shot.target.collider.center(),
+            shot.shooter, shot.team, shot.damage, shot.targets, stats);
+        if stats.homing {
+            new_projectile.target_id = Some(shot.target.id);
+        }
+
+        new_projectile
+    }
+
+    //Flies to a spot on the ground, never homing
+    pub fn at_point(origin: Point, point: Point, shooter: u32, team: i32, damage: i32,
+            targets: WeaponTargets, stats: ProjectileStats) -> Projectile {
+        let mut new_projectile = Projectile {
+            shooter,
+            team,
+            position: (origin.x as f32, origin.y as f32),
+            velocity: (0.0, 0.0),
+            target_point: (point.x as f32, point.y as f32),
+            target_id: None,
+            damage,
+            targets,
+            stats,
+            done: false,
+        };
+        new_projectile.aim();
+
+        new_projectile
+    }
+
+    fn aim<'f>(&'f mut self) {
+        let dx = self.target_point.0 - self.position.0;
+        let dy = self.target_point.1 - self.position.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+
+        self.velocity = if distance > 0.0 {
+            (dx / distance * self.stats.speed, dy / distance * self.stats.speed)
+        } else {
+            (0.0, 0.0)
+        };
+    }
+
+    //Moves one frame, pushing the damage it deals into hits once it lands
+    pub fn update<'f>(&'f mut self, targets: &'f [Target], hits: &'f mut Vec<Hit>) {
+        if self.done {
+            return;
+        }
+
+        //Homing projectiles follow their target, and fly on to where it died
+        if let Some(target) = self.target_id
+                .and_then(|id| targets.iter().find(|target| target.id == id)) {
+            let centre = target.collider.center();
+            self.target_point = (centre.x as f32, centre.y as f32);
+            self.aim();
+        }
+
+        let dx = self.target_point.0 - self.position.0;
+        let dy = self.target_point.1 - self.position.1;
+        if (dx * dx + dy * dy).sqrt() <= self.stats.speed {
+            self.position = self.target_point;
+            self.impact(targets, hits);
+            return;
+        }
+
+        self.position.0 += self.velocity.0;
+        self.position.1 += self.velocity.1;
+    }
+
+    fn impact<'f>(&'f mut self, targets: &'f [Target], hits: &'f mut Vec<Hit>) {
+        self.done = true;
+
+        let point = self.get_location();
+        let area = Rect::from_center(point, 1, 1);
+        let can_hit = |target: &&Target| target.id != self.shooter &&
+            (target.team != self.team || self.stats.friendly_fire) &&
+            self.targets.allows(target.collider_type);
+
+        if self.stats.splash_radius > 0.0 {
+            targets.iter()
+                .filter(can_hit)
+                .filter(|target| combat::edge_distance(area, target.collider) <=
+                    self.stats.splash_radius)
+                .for_each(|target| hits.push(Hit { id: target.id, damage: self.damage }));
+        } else {
+            let direct = match self.target_id {
+                Some(id) => targets.iter().filter(can_hit).find(|target| target.id == id),
+                None => targets.iter()
+                    .filter(can_hit)
+                    .find(|target| target.collider.contains_point(point)),
+            };
+            if let Some(target) = direct {
+                hits.push(Hit { id: target.id, damage: self.damage });
+            }
+        }
+    }
+
+    pub fn get_location<'f>(&'f self) -> Point {
+        Point::new(self.position.0.round() as i32, self.position.1.round() as i32)
+    }
+}
+
+impl Renderable for Projectile {
+    fn render<'f>(&'f self, _tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
+        let previous_color = canvas.draw_color();
+        canvas.set_draw_color(Color::RGBA(255, 220, 60, 255));
+        let _ = canvas.fill_rect(self.get_loc_rect());
+        canvas.set_draw_color(previous_color);
+    }
+
+    fn get_loc_rect<'f>(&'f self) -> Rect {
+        Rect::from_center(self.get_location(), PROJECTILE_SIZE, PROJECTILE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::Collidable;
+
+    fn target(id: u32, team: i32, x: i32, collider_type: Collidable) -> Target {
+        Target { id, team, collider: Rect::new(x, 0, 10, 10), collider_type }
+    }
+
+    //Lands a splash shell from shooter 1 on team 0 at (5, 5) and returns who it hit
+    fn splash<'f>(targets: &'f [Target], weapon_targets: WeaponTargets, friendly_fire: bool)
+            -> Vec<u32> {
+        let stats = ProjectileStats::new(100.0, false, 20.0, friendly_fire);
+        let mut projectile = Projectile::at_point(Point::new(5, 50), Point::new(5, 5), 1, 0, 10,
+            weapon_targets, stats);
+        let mut hits = vec![];
+        projectile.update(targets, &mut hits);
+
+        assert!(projectile.done);
+        hits.iter().map(|hit| hit.id).collect()
+    }
+
+    #[test]
+    fn ground_splash_leaves_air_untouched() {
+        let targets = [
+            target(2, 1, 0, Collidable::GroundCollidable),
+            target(3, 1, 5, Collidable::AirUncollidable),
+            target(4, 1, 10, Collidable::AbsoluteCollidable),
+        ];
+
+        assert_eq!(splash(&targets, WeaponTargets::Ground, false), vec![2, 4]);
+        assert_eq!(splash(&targets, WeaponTargets::Air, false), vec![3, 4]);
+        assert_eq!(splash(&targets, WeaponTargets::Both, false), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn friendly_fire_spares_the_shooter() {
+        let targets = [
+            target(1, 0, 0, Collidable::GroundCollidable),
+            target(2, 0, 5, Collidable::GroundCollidable),
+            target(3, 1, 10, Collidable::GroundCollidable),
+        ];
+
+        assert_eq!(splash(&targets, WeaponTargets::Ground, false), vec![3]);
+        assert_eq!(splash(&targets, WeaponTargets::Ground, true), vec![2, 3]);
+    }
+}
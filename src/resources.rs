@@ -0,0 +1,179 @@
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+use strum_macros::{Display, EnumString};
+
+use std::fmt;
+
+use crate::general::{self, Renderable};
+use crate::sprite::{Sprite, TextureManager, TextureType};
+
+//Price of anything bought through a ButtonFunction
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Cost {
+    pub minerals: i32,
+    pub gas: i32,
+}
+
+impl Cost {
+    pub const fn new(minerals: i32, gas: i32) -> Cost {
+        Cost { minerals, gas }
+    }
+
+    //Share of the cost, rounded down
+    pub fn percent<'f>(&'f self, percent: i32) -> Cost {
+        Cost::new(self.minerals * percent / 100, self.gas * percent / 100)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceError {
+    NotEnoughMinerals,
+    NotEnoughGas,
+    SupplyBlocked,
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::NotEnoughMinerals => { write!(f, "Not enough minerals") },
+            ResourceError::NotEnoughGas => { write!(f, "Not enough gas") },
+            ResourceError::SupplyBlocked => { write!(f, "Supply blocked") },
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {}
+
+//Per player stockpile
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResourceBank {
+    pub minerals: i32,
+    pub gas: i32,
+}
+
+impl ResourceBank {
+    pub fn new(minerals: i32, gas: i32) -> ResourceBank {
+        ResourceBank { minerals, gas }
+    }
+
+    pub fn can_afford<'f>(&'f self, cost: Cost) -> Result<(), ResourceError> {
+        if self.minerals < cost.minerals {
+            return Err(ResourceError::NotEnoughMinerals);
+        }
+        if self.gas < cost.gas {
+            return Err(ResourceError::NotEnoughGas);
+        }
+        Ok(())
+    }
+
+    pub fn spend<'f>(&'f mut self, cost: Cost) -> Result<(), ResourceError> {
+        self.can_afford(cost)?;
+        self.minerals -= cost.minerals;
+        self.gas -= cost.gas;
+        Ok(())
+    }
+
+    pub fn refund<'f>(&'f mut self, cost: Cost) {
+        self.minerals += cost.minerals;
+        self.gas += cost.gas;
+    }
+
+    pub fn deposit<'f>(&'f mut self, kind: ResourceKind, amount: i32) {
+        match kind {
+            ResourceKind::Minerals => { self.minerals += amount; },
+            ResourceKind::Gas => { self.gas += amount; },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ResourceKind {
+    Minerals,
+    Gas,
+}
+
+#[allow(unreachable_patterns)]
+impl ResourceKind {
+    pub fn get_w<'f>(&'f self) -> u32 {
+        match self {
+            ResourceKind::Minerals => { 50 },
+            ResourceKind::Gas => { 100 },
+            _ => { unimplemented!() }
+        }
+    }
+
+    pub fn get_h<'f>(&'f self) -> u32 {
+        match self {
+            ResourceKind::Minerals => { 50 },
+            ResourceKind::Gas => { 100 },
+            _ => { unimplemented!() }
+        }
+    }
+
+    //Amount a worker carries back per trip
+    pub fn get_load<'f>(&'f self) -> i32 {
+        match self {
+            ResourceKind::Minerals => { general::MINERAL_LOAD },
+            ResourceKind::Gas => { general::GAS_LOAD },
+            _ => { unimplemented!() }
+        }
+    }
+
+    pub fn get_default_amount<'f>(&'f self) -> i32 {
+        match self {
+            ResourceKind::Minerals => { general::MINERAL_FIELD_AMOUNT },
+            ResourceKind::Gas => { general::GAS_FIELD_AMOUNT },
+            _ => { unimplemented!() }
+        }
+    }
+}
+
+//Mineral patch or gas geyser, gone once amount reaches 0
+#[derive(Clone, Copy)]
+pub struct ResourceField {
+    pub id: u32,
+    pub kind: ResourceKind,
+    pub collider: Rect,
+    pub amount: i32,
+    pub harvester: Option<u32>, // Id of the worker mining it this frame
+    pub extractor: Option<u32>, // Id of the refinery built on a geyser
+    pub sprite: Sprite,
+}
+
+impl ResourceField {
+    pub fn new<'f>(kind: ResourceKind, location: Point, amount: i32,
+            atlas: &'f TextureManager) -> ResourceField {
+        let collider = Rect::new(location.x, location.y, kind.get_w(), kind.get_h());
+        ResourceField {
+            id: general::next_entity_id(),
+            kind,
+            collider,
+            amount,
+            harvester: None,
+            extractor: None,
+            sprite: Sprite::new(collider, TextureType::Resource { kind }, atlas),
+        }
+    }
+
+    //Removes up to load from the field, returns how much was taken
+    pub fn take<'f>(&'f mut self, load: i32) -> i32 {
+        let taken = load.min(self.amount);
+        self.amount -= taken;
+        taken
+    }
+
+    pub fn depleted<'f>(&'f self) -> bool {
+        self.amount <= 0
+    }
+}
+
+impl Renderable for ResourceField {
+    fn render<'f>(&'f self, tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
+        self.sprite.render(tx_mgr, canvas);
+    }
+
+    fn get_loc_rect<'f>(&'f self) -> Rect {
+        self.collider
+    }
+}
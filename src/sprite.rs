@@ -12,12 +12,17 @@ use std::path::PathBuf;
 use std::env;
 use std::collections::hash_map::HashMap;
 use std::cmp::max;
+use std::str::FromStr;
 
 use crate::building::BuildingType;
+use crate::unit::UnitType;
 use crate::general::{Faction, Renderable};
+use crate::resources::ResourceKind;
 
 const TEXTURE_BUILDING_WIDTH: u32 = 128;
 const TEXTURE_BUILDING_HEIGHT: u32 = 128;
+const TEXTURE_UNIT_WIDTH: u32 = 64;
+const TEXTURE_UNIT_HEIGHT: u32 = 64;
 const TEXTURE_UI_WIDTH: u32 = 64;
 const TEXTURE_UI_HEIGHT: u32 = 64;
 const TEXTURE_WORLD_WIDTH: u32 = 64;
@@ -96,8 +101,10 @@ impl Renderable for Sprite {
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Display)]
 pub enum TextureType {
     Building {faction: Faction, b_type: BuildingType},
+    Unit {faction: Faction, u_type: UnitType},
     World {tile_index: usize},
     UI {type_index: usize},
+    Resource {kind: ResourceKind},
     Filler
 }
 
@@ -111,6 +118,11 @@ impl TextureType {
                     faction, b_type).as_str());
                 string
             },
+            TextureType::Unit { faction, u_type } => {
+                string.push_str(format!(": fc-{} u_type-{}", 
+                    faction, u_type).as_str());
+                string
+            },
             TextureType::UI { type_index } => {
                 string.push_str(format!(": t_index-{}", 
                     type_index).as_str());
@@ -121,6 +133,11 @@ impl TextureType {
                     tile_index).as_str());
                 string
             },
+            TextureType::Resource { kind } => {
+                string.push_str(format!(": kind-{}", 
+                    kind).as_str());
+                string
+            },
             TextureType::Filler => {
                 string
             },
@@ -155,6 +172,10 @@ impl<'t> TextureManager<'t> {
                     println!("  [[{key}: {b_type} {faction}] : [x:{} y:{} w:{} h:{}]]", 
                         value.x, value.y, value.w, value.h);
                 },
+                TextureType::Unit { faction, u_type } => {
+                    println!("  [[{key}: {u_type} {faction}] : [x:{} y:{} w:{} h:{}]]", 
+                        value.x, value.y, value.w, value.h);
+                },
                 TextureType::World { tile_index } => {
                     println!("  [[{key}: {tile_index}] : [x:{} y:{} w:{} h:{}]]", 
                         value.x, value.y, value.w, value.h);
@@ -163,6 +184,10 @@ impl<'t> TextureManager<'t> {
                     println!("  [[{key}: {type_index}] : [x:{} y:{} w:{} h:{}]]", 
                         value.x, value.y, value.w, value.h);
                 },
+                TextureType::Resource { kind } => {
+                    println!("  [[{key}: {kind}] : [x:{} y:{} w:{} h:{}]]", 
+                        value.x, value.y, value.w, value.h);
+                },
                 TextureType::Filler => {
                     println!("  [[{key}] : [x:{} y:{} w:{} h:{}]]", 
                         value.x, value.y, value.w, value.h);
@@ -215,6 +240,16 @@ impl<'t> TextureManager<'t> {
                         TEXTURE_BUILDING_WIDTH,
                         TEXTURE_BUILDING_HEIGHT ) 
                 },
+                TextureType::Unit { .. } => {
+                    Rect::new(
+                        value.unwrap().x, 
+                        value.unwrap().y, 
+                        TEXTURE_UNIT_WIDTH,
+                        TEXTURE_UNIT_HEIGHT ) 
+                },
+                TextureType::Resource { .. } => {
+                    value.unwrap().to_owned()
+                },
                 TextureType::Filler => {
                     Rect::new(0, 0, 32, 32)
                 },
@@ -258,6 +293,17 @@ impl<'t> TextureManager<'t> {
         }
     }
 
+    fn get_unit_type_from_string<'f>(&'f self, str: String) -> UnitType {
+        let parts: Vec<&str> = str.split(&['_', '.'][..]).collect();
+        let u_type = parts[1].to_lowercase();
+
+        match UnitType::get_all_variants().into_iter()
+                .find(|variant| variant.to_string().to_lowercase() == u_type) {
+            Some(variant) => variant,
+            None => { panic!("Unknown unit type!") }
+        }
+    }
+
     fn get_wh_of_texture<'f>(&'f self, str: String) -> (u32, u32) {
         let temp_texture = self.texture_creator.load_texture(str).unwrap();
         (temp_texture.query().width, temp_texture.query().height)
@@ -283,6 +329,13 @@ impl<'t> TextureManager<'t> {
                     b_type:
                         self.get_building_type_from_string(name.to_owned()) }
             },
+            "units" => {
+                TextureType::Unit { 
+                    faction: 
+                        self.get_faction_from_string(name.to_owned()),
+                    u_type:
+                        self.get_unit_type_from_string(name.to_owned()) }
+            },
             "ground" => {
                 TextureType::World { 
                     tile_index: {
@@ -300,10 +353,16 @@ impl<'t> TextureManager<'t> {
                         match name.as_str() {
                             "buttons" => { 0 },
                             "bottom_left_ui" => { 1 },
+                            "font" => { 2 },
                             _ => { panic!("Unknown UI name!") }
                         }
                     } }
             },
+            "resources" => {
+                TextureType::Resource {
+                    kind: ResourceKind::from_str(name.as_str())
+                        .expect("Unknown resource name!") }
+            },
             "filler" => {
                 TextureType::Filler 
             },
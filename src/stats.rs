@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::building::BuildingType;
+use crate::combat::{Weapon, WeaponTargets};
+use crate::datafile::{self, DataError, Entity, Global, expect_args, at_least, parse_bool};
+use crate::general::Collidable;
+use crate::projectile::ProjectileStats;
+use crate::resources::Cost;
+use crate::unit::UnitType;
+
+//Entity stats file format (plain text, one stat per line, '#' starts a comment):
+//
+//  version 1
+//  unit <unit type>             (the stats below belong to this unit until the next entry)
+//  building <building type>
+//  size <w> <h>                 (pixels, buildings cover whole 25px cells)
+//  hp <max hp>
+//  armor <armor>
+//  energy <max energy>
+//  supply <supply>              (taken up by a unit, added to the cap by a building)
+//  cost <minerals> <gas>
+//  build_time <ms>
+//  sight <pixels>
+//  speed <pixels per frame>     (units, optional for buildings that can lift off and fly)
+//  collider <collidable type>   (units only)
+//  weapon <range> <damage> <cooldown ms> <Ground|Air|Both>              (optional)
+//  projectile <speed> <homing> <splash radius> <friendly fire>         (optional, after weapon)
+//
+//Every unit and building type needs an entry that sets each of its stats once.
+pub const STATS_FORMAT_VERSION: u32 = 1;
+
+const SHARED_STATS: [&str; 8] =
+    ["size", "hp", "armor", "energy", "supply", "cost", "build_time", "sight"];
+const UNIT_STATS: [&str; 2] = ["speed", "collider"];
+const BUILDING_STATS: [&str; 1] = ["speed"]; // Optional, buildings without it stay put
+const OPTIONAL_STATS: [&str; 2] = ["weapon", "projectile"];
+
+static STATS: Global<StatsFile> = Global::new("Entity stats");
+
+#[derive(Debug)]
+pub enum StatsError {
+    File(DataError),
+    MissingStat { entity: String, stat: &'static str },
+}
+
+impl fmt::Display for StatsError {
+    fn fmt<'f>(&'f self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsError::File(err) => { write!(f, "{}", err) },
+            StatsError::MissingStat { entity, stat } => {
+                write!(f, "{} has no '{}' stat", entity, stat)
+            },
+        }
+    }
+}
+
+impl std::error::Error for StatsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StatsError::File(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DataError> for StatsError {
+    fn from(err: DataError) -> StatsError {
+        StatsError::File(err)
+    }
+}
+
+//Balance numbers of one unit or building type, buildings leave collider alone
+#[derive(Clone, Copy)]
+pub struct EntityStats {
+    pub w: u32,
+    pub h: u32,
+    pub max_hp: i32,
+    pub armor: i32,
+    pub max_energy: f32,
+    pub supply: i32,
+    pub cost: Cost,
+    pub build_time: Duration,
+    #[allow(dead_code)] // Nothing is hidden from view yet
+    pub sight: u32,
+    pub speed: f32, // Pixels per frame, a building's while flying
+    pub collider_type: Collidable,
+    pub weapon: Option<Weapon>,
+}
+
+impl EntityStats {
+    fn new() -> EntityStats {
+        EntityStats {
+            w: 0,
+            h: 0,
+            max_hp: 0,
+            armor: 0,
+            max_energy: 0.0,
+            supply: 0,
+            cost: Cost::default(),
+            build_time: Duration::ZERO,
+            sight: 0,
+            speed: 0.0,
+            collider_type: Collidable::GroundCollidable,
+            weapon: None,
+        }
+    }
+}
+
+fn takes_stat<'f>(entry: Entity, stat: &'f str) -> Option<&'static str> {
+    let own_stats: &[&'static str] = match entry {
+        Entity::Unit(..) => &UNIT_STATS,
+        Entity::Building(..) => &BUILDING_STATS,
+    };
+
+    SHARED_STATS.iter().chain(own_stats.iter()).chain(OPTIONAL_STATS.iter())
+        .find(|known| **known == stat)
+        .copied()
+}
+
+fn required_stats(entry: Entity) -> Vec<&'static str> {
+    match entry {
+        Entity::Unit(..) => SHARED_STATS.iter().chain(UNIT_STATS.iter()).copied().collect(),
+        Entity::Building(..) => SHARED_STATS.to_vec(),
+    }
+}
+
+//Stats of every unit and building type, loaded once at startup
+pub struct StatsFile {
+    pub units: HashMap<UnitType, EntityStats>,
+    pub buildings: HashMap<BuildingType, EntityStats>,
+}
+
+impl StatsFile {
+    pub fn load<'f>(path: &'f Path) -> Result<StatsFile, StatsError> {
+        StatsFile::parse(&datafile::read(path)?)
+    }
+
+    pub fn parse<'f>(contents: &'f str) -> Result<StatsFile, StatsError> {
+        let mut lines = datafile::lines(contents);
+        datafile::check_version(&mut lines, STATS_FORMAT_VERSION)?;
+
+        let mut stats = StatsFile { units: HashMap::new(), buildings: HashMap::new() };
+        //Entry being read, with the stats it set so far
+        let mut current: Option<(Entity, EntityStats, Vec<&'static str>)> = None;
+
+        for (line_nr, line) in lines {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[0] {
+                "unit" | "building" => {
+                    expect_args(line_nr, &parts, 1)?;
+                    if let Some(done) = current.take() {
+                        stats.insert(done)?;
+                    }
+
+                    let entry = datafile::parse_entity(line_nr, parts[0], parts[1])?;
+                    if stats.has(entry) {
+                        return Err(DataError::Parse {
+                            line: line_nr,
+                            message: format!("{} is listed twice", entry),
+                        }.into());
+                    }
+                    current = Some((entry, EntityStats::new(), vec![]));
+                },
+                stat => {
+                    let (entry, entity, set) = match current.as_mut() {
+                        Some(current) => current,
+                        None => {
+                            return Err(DataError::Parse {
+                                line: line_nr,
+                                message: format!("'{}' comes before any unit or building",
+                                    stat),
+                            }.into());
+                        }
+                    };
+
+                    let stat = match takes_stat(*entry, stat) {
+                        Some(stat) => stat,
+                        None => {
+                            return Err(DataError::Parse {
+                                line: line_nr,
+                                message: format!("{} has no '{}' stat", entry, stat),
+                            }.into());
+                        }
+                    };
+                    if set.contains(&stat) {
+                        return Err(DataError::Parse {
+                            line: line_nr,
+                            message: format!("'{}' is set twice for {}", stat, entry),
+                        }.into());
+                    }
+
+                    parse_stat(line_nr, *entry, &parts, entity)?;
+                    set.push(stat);
+                }
+            }
+        }
+
+        if let Some(done) = current.take() {
+            stats.insert(done)?;
+        }
+
+        if let Some(entry) = Entity::get_all_variants().into_iter().find(|e| !stats.has(*e)) {
+            return Err(DataError::MissingEntity(entry.to_string()).into());
+        }
+
+        Ok(stats)
+    }
+
+    fn has<'f>(&'f self, entry: Entity) -> bool {
+        match entry {
+            Entity::Unit(unit_type) => { self.units.contains_key(&unit_type) },
+            Entity::Building(building_type) => { self.buildings.contains_key(&building_type) },
+        }
+    }
+
+    fn insert<'f>(&'f mut self, (entry, entity, set): (Entity, EntityStats, Vec<&'static str>))
+            -> Result<(), StatsError> {
+        if let Some(stat) = required_stats(entry).into_iter().find(|stat| !set.contains(stat)) {
+            return Err(StatsError::MissingStat { entity: entry.to_string(), stat });
+        }
+
+        match entry {
+            Entity::Unit(unit_type) => { self.units.insert(unit_type, entity); },
+            Entity::Building(building_type) => { self.buildings.insert(building_type, entity); },
+        }
+        Ok(())
+    }
+}
+
+//Makes the stats available to every UnitType and BuildingType, only the first call counts
+pub fn init(stats: StatsFile) {
+    STATS.init(stats);
+}
+
+pub fn unit(unit_type: UnitType) -> &'static EntityStats {
+    &STATS.get().units[&unit_type]
+}
+
+pub fn building(building_type: BuildingType) -> &'static EntityStats {
+    &STATS.get().buildings[&building_type]
+}
+
+fn parse_stat<'f>(line: usize, entry: Entity, parts: &'f [&'f str],
+        entity: &'f mut EntityStats) -> Result<(), DataError> {
+    match parts[0] {
+        "size" => {
+            expect_args(line, parts, 2)?;
+            entity.w = at_least(line, parts[1], 1)?;
+            entity.h = at_least(line, parts[2], 1)?;
+            if matches!(entry, Entity::Building(..)) &&
+                    (!entity.w.is_multiple_of(25) || !entity.h.is_multiple_of(25)) {
+                return Err(DataError::Parse {
+                    line,
+                    message: "building sizes must be multiples of 25".to_string(),
+                });
+            }
+        },
+        "hp" => {
+            expect_args(line, parts, 1)?;
+            entity.max_hp = at_least(line, parts[1], 1)?;
+        },
+        "armor" => {
+            expect_args(line, parts, 1)?;
+            entity.armor = at_least(line, parts[1], 0)?;
+        },
+        "energy" => {
+            expect_args(line, parts, 1)?;
+            entity.max_energy = at_least(line, parts[1], 0.0)?;
+        },
+        "supply" => {
+            expect_args(line, parts, 1)?;
+            entity.supply = at_least(line, parts[1], 0)?;
+        },
+        "cost" => {
+            expect_args(line, parts, 2)?;
+            entity.cost = Cost::new(at_least(line, parts[1], 0)?, at_least(line, parts[2], 0)?);
+        },
+        "build_time" => {
+            expect_args(line, parts, 1)?;
+            entity.build_time = Duration::from_millis(at_least(line, parts[1], 1)?);
+        },
+        "sight" => {
+            expect_args(line, parts, 1)?;
+            entity.sight = at_least(line, parts[1], 0)?;
+        },
+        "speed" => {
+            expect_args(line, parts, 1)?;
+            entity.speed = at_least(line, parts[1], 0.0)?;
+        },
+        "collider" => {
+            expect_args(line, parts, 1)?;
+            entity.collider_type = Collidable::from_str(parts[1]).map_err(|_|
+                DataError::Parse {
+                    line,
+                    message: format!("unknown collidable type '{}'", parts[1]),
+                })?;
+        },
+        "weapon" => {
+            expect_args(line, parts, 4)?;
+            let targets = WeaponTargets::from_str(parts[4]).map_err(|_| DataError::Parse {
+                line,
+                message: format!("weapon targets must be Ground, Air or Both, not '{}'",
+                    parts[4]),
+            })?;
+            entity.weapon = Some(Weapon::new(at_least(line, parts[1], 0.0)?,
+                at_least(line, parts[2], 1)?, at_least(line, parts[3], 1)?, targets));
+        },
+        "projectile" => {
+            expect_args(line, parts, 4)?;
+            let stats = ProjectileStats::new(at_least(line, parts[1], 0.0)?,
+                parse_bool(line, parts[2])?, at_least(line, parts[3], 0.0)?,
+                parse_bool(line, parts[4])?);
+            match entity.weapon {
+                Some(weapon) => { entity.weapon = Some(weapon.with_projectile(stats)); },
+                None => {
+                    return Err(DataError::Parse {
+                        line,
+                        message: "'projectile' needs a 'weapon' line before it".to_string(),
+                    });
+                }
+            }
+        },
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafile::testing::{self, swapped, line_of};
+
+    const SHIPPED: &str = include_str!("../assets/data/entities.stats");
+
+    fn assert_parse_error<'f>(contents: &'f str, line: usize, expected: &'f str) {
+        match StatsFile::parse(contents).err() {
+            Some(StatsError::File(err)) => { testing::assert_parse_error(&err, line, expected); },
+            Some(err) => { panic!("expected a parse error, got '{}'", err); },
+            None => { panic!("stats parsed without an error"); }
+        }
+    }
+
+    #[test]
+    fn shipped_stats_parse() {
+        let stats = StatsFile::parse(SHIPPED).unwrap();
+
+        assert_eq!(stats.units.len(), UnitType::get_all_variants().len());
+        assert_eq!(stats.buildings.len(), BuildingType::get_all_variants().len());
+
+        let worker = &stats.units[&UnitType::Worker];
+        assert_eq!((worker.w, worker.h, worker.max_hp), (25, 25, 40));
+        assert!(worker.weapon.is_some_and(|weapon| weapon.projectile.is_none()));
+        let marine = &stats.units[&UnitType::Marine];
+        assert!(marine.weapon.is_some_and(|weapon| weapon.projectile.is_some()));
+    }
+
+    #[test]
+    fn stat_before_any_entity() {
+        let contents = swapped(SHIPPED, "version 1", "version 1\nhp 10");
+
+        assert_parse_error(&contents, 3, "'hp' comes before any unit or building");
+    }
+
+    #[test]
+    fn stat_the_entity_does_not_have() {
+        let contents = swapped(SHIPPED, "building Barracks",
+            "building Barracks\n    collider GroundCollidable");
+
+        assert_parse_error(&contents, line_of(&contents, "building Barracks") + 1,
+            "building Barracks has no 'collider' stat");
+    }
+
+    #[test]
+    fn duplicate_stat() {
+        let contents = swapped(SHIPPED, "    hp 40\n", "    hp 40\n    hp 50\n");
+
+        assert_parse_error(&contents, line_of(&contents, "hp 50"),
+            "'hp' is set twice for unit Worker");
+    }
+
+    #[test]
+    fn duplicate_entity() {
+        let contents = format!("{}\nunit Worker\n", SHIPPED);
+
+        assert_parse_error(&contents, contents.lines().count(), "unit Worker is listed twice");
+    }
+
+    #[test]
+    fn missing_stat() {
+        let contents = swapped(SHIPPED, "    sight 200\n", "");
+
+        match StatsFile::parse(&contents).err() {
+            Some(StatsError::MissingStat { entity, stat }) => {
+                assert_eq!(entity, "unit Worker");
+                assert_eq!(stat, "sight");
+            },
+            _ => { panic!("expected a missing stat"); }
+        }
+    }
+
+    #[test]
+    fn missing_entity() {
+        let end = SHIPPED.find("building SupplyDepot").unwrap();
+        match StatsFile::parse(&SHIPPED[..end]).err() {
+            Some(StatsError::File(DataError::MissingEntity(entity))) => {
+                assert_eq!(entity, "building SupplyDepot");
+            },
+            _ => { panic!("expected a missing entity"); }
+        }
+    }
+
+    #[test]
+    fn projectile_before_weapon() {
+        let contents = swapped(SHIPPED,
+            "    weapon 125 6 860 Both\n    projectile 14 true 0 false\n",
+            "    projectile 14 true 0 false\n    weapon 125 6 860 Both\n");
+
+        assert_parse_error(&contents, line_of(&contents, "projectile 14"),
+            "'projectile' needs a 'weapon' line before it");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::general::Collidable;
+use crate::player::Player;
+
+const SEPARATION_PADDING: f32 = 2.0;
+const COHESION_RANGE: f32 = 120.0;
+const COHESION_STRENGTH: f32 = 0.02;
+//Same destination if the move targets are this close (pixels)
+const SAME_DESTINATION: f32 = 30.0;
+
+//Snapshot of a unit used for steering, positions are centres in pixels
+#[derive(Clone, Copy)]
+pub struct Body {
+    pub position: (f32, f32),
+    pub radius: f32,
+    pub collider_type: Collidable,
+    pub moving: bool,
+    pub team: i32,
+    pub group: Option<u32>, // Flow field id, only unique within a team
+    pub destination: Option<(f32, f32)>,
+}
+
+//Offset that pushes bodies[index] away from overlapping bodies it collides with and,
+//while moving as part of a group, slightly towards the rest of that group.
+//Idle bodies give way to moving ones so arrivals spread into a clump.
+pub fn steer<'f>(bodies: &'f [Body], index: usize, max_push: f32) -> (f32, f32) {
+    let body = bodies[index];
+    let mut push = (0.0, 0.0);
+    let mut group_centre = (0.0, 0.0);
+    let mut group_size: f32 = 0.0;
+
+    let mut i: usize = 0;
+    while i < bodies.len() {
+        let other = bodies[i];
+        if i == index {
+            i += 1;
+            continue;
+        }
+
+        let dx = body.position.0 - other.position.0;
+        let dy = body.position.1 - other.position.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+
+        if body.collider_type.collides_with(other.collider_type) {
+            let min_distance = body.radius + other.radius + SEPARATION_PADDING;
+            if distance < min_distance {
+                //Bodies on the exact same spot split by index so they don't stay stuck
+                let direction = if distance > 0.01 {
+                    (dx / distance, dy / distance)
+                } else {
+                    let angle = (index as f32 - i as f32) * 2.399;
+                    (angle.cos(), angle.sin())
+                };
+                let weight = match (body.moving, other.moving) {
+                    (false, true) => { 0.9 },
+                    (true, false) => { 0.2 },
+                    _ => { 0.5 },
+                };
+                let overlap = (min_distance - distance) * weight *
+                    body.collider_type.push_strength();
+                push.0 += direction.0 * overlap;
+                push.1 += direction.1 * overlap;
+            }
+        }
+
+        if body.moving && body.group.is_some() && body.team == other.team &&
+                body.group == other.group && distance < COHESION_RANGE {
+            group_centre.0 += other.position.0;
+            group_centre.1 += other.position.1;
+            group_size += 1.0;
+        }
+
+        i += 1;
+    }
+
+    if group_size > 0.0 {
+        push.0 += (group_centre.0 / group_size - body.position.0) * COHESION_STRENGTH;
+        push.1 += (group_centre.1 / group_size - body.position.1) * COHESION_STRENGTH;
+    }
+
+    let length = (push.0 * push.0 + push.1 * push.1).sqrt();
+    if length > max_push {
+        push = (push.0 / length * max_push, push.1 / length * max_push);
+    }
+
+    push
+}
+
+//Bodies of every player's units, player by player in unit order, so units of different
+//teams push each other apart too
+pub fn collect_bodies<'f>(players: &'f [Player]) -> Vec<Body> {
+    players.iter()
+        .flat_map(|player| player.units.iter())
+        .map(|unit| unit.get_body())
+        .collect()
+}
+
+//A moving body that bumps into one of its team's already idling at the same
+//destination counts as arrived, that's how a group settles into a clump instead
+//of fighting for one pixel
+pub fn blocked_by_arrived<'f>(bodies: &'f [Body], index: usize) -> bool {
+    let body = bodies[index];
+    let destination = match body.destination {
+        Some(destination) if body.moving => destination,
+        _ => { return false; }
+    };
+
+    bodies.iter().enumerate().any(|(i, other)| {
+        if i == index || other.moving || other.team != body.team ||
+                !body.collider_type.collides_with(other.collider_type) {
+            return false;
+        }
+
+        let touching = distance(body.position, other.position) <=
+            body.radius + other.radius + SEPARATION_PADDING * 2.0;
+        let same_destination = other.destination
+            .is_some_and(|other_dest| distance(destination, other_dest) < SAME_DESTINATION);
+
+        touching && same_destination
+    })
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt()
+}
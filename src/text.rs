@@ -0,0 +1,33 @@
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+
+use crate::sprite::{TextureManager, TextureType};
+
+//Bitmap font from assets/sprites/UI/font.png, a 5x7 glyph every 6 pixels in this order
+const GLYPHS: &str = " 0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ:/-.!";
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_ADVANCE: i32 = 6;
+
+//Width in pixels of text drawn at scale
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as i32 * GLYPH_ADVANCE - 1).max(0) as u32 * scale
+}
+
+//Draws text with its top left corner at location, unknown characters are left blank
+pub fn render_text<'f>(text: &'f str, location: Point, scale: u32,
+        tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
+    let font = tx_mgr.get_rect_raw(TextureType::UI { type_index: 2 });
+
+    for (i, character) in text.to_uppercase().chars().enumerate() {
+        let glyph = match GLYPHS.find(character) {
+            Some(glyph) if glyph > 0 => glyph as i32,
+            _ => { continue; }
+        };
+
+        let _ = canvas.copy(tx_mgr.get_atlas_ref(),
+            Rect::new(font.x + glyph * GLYPH_ADVANCE, font.y, GLYPH_WIDTH, GLYPH_HEIGHT),
+            Rect::new(location.x + i as i32 * GLYPH_ADVANCE * scale as i32, location.y,
+                GLYPH_WIDTH * scale, GLYPH_HEIGHT * scale));
+    }
+}
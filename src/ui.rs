@@ -1,8 +1,18 @@
 use sdl2::render::WindowCanvas;
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 
 use crate::{Sprite, Collidable, sprite::TextureManager};
 use crate::general::Renderable;
+use crate::ability::AbilityKind;
+use crate::resources::Cost;
+use crate::unit::UnitType;
+use crate::building::BuildingType;
+use crate::text;
+
+use sdl2::keyboard::Keycode;
+use stopwatch::Stopwatch;
+use strum_macros::EnumString;
+use std::time::Duration;
 
 use std::cmp::max;
 
@@ -49,13 +59,17 @@ impl Renderable for UiElement {
 pub struct Button {
     pub ui: UiElement,
     pub btn_function: ButtonFunction, 
+    pub hotkey: Option<Keycode>,
+    pub opens: Option<usize>, // Panel of the same command card shown after pressing it
 }
 
 impl Button {
     pub fn new<'f>(ui_elem: UiElement, btn_function: ButtonFunction) -> Button {
         Button {
             ui: ui_elem,
-            btn_function
+            btn_function,
+            hotkey: None,
+            opens: None,
         }
     }
 }
@@ -197,34 +211,96 @@ pub enum YAlignment {
     None
 }
 
+const NOTIFICATION_TIME: Duration = Duration::from_millis(2500);
+
+//Message shown above the bottom of the screen for a few seconds
+#[derive(Clone)]
+pub struct Notification {
+    pub message: String,
+    timer: Stopwatch,
+}
+
+impl Notification {
+    pub fn new(message: String) -> Notification {
+        Notification { message, timer: Stopwatch::start_new() }
+    }
+
+    pub fn expired<'f>(&'f self) -> bool {
+        self.timer.elapsed() >= NOTIFICATION_TIME
+    }
+
+    pub fn render<'f>(&'f self, tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
+        let viewport = canvas.viewport();
+        let width = text::text_width(self.message.as_str(), 3) as i32;
+        text::render_text(self.message.as_str(),
+            Point::new((viewport.width() as i32 - width) / 2, viewport.height() as i32 - 360),
+            3, tx_mgr, canvas);
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, EnumString)]
 pub enum ButtonFunction {
-    ShowTier1Buildings,
-    ShowTier2Buildings,
+    OpenPanel,
     MakeWorker,
     Back,
-    MakeBarracks,
-    MakeCC,
-    PlaceConstruction,
     PlaceBarracks,
     PlaceCommandCentre,
+    PlaceRefinery,
+    PlaceSupplyDepot,
+    CancelConstruction,
+    LiftOff,
+    Land,
+    Stop,
+    MakeMarine,
+    MakeWraith,
+    #[strum(disabled)] // Named by its AbilityKind in command card files
+    Ability(AbilityKind),
 }
 
 #[allow(unreachable_patterns)]
 impl ButtonFunction {
     pub fn get_texture_index<'f>(&'f self) -> i32 {
         match self {
-            ButtonFunction::ShowTier1Buildings => { 0 },
-            ButtonFunction::ShowTier2Buildings => { 1 },
+            ButtonFunction::OpenPanel => { 0 },
             ButtonFunction::MakeWorker => { 5 },
             ButtonFunction::Back => { 2 },
-            ButtonFunction::MakeBarracks => { 4 },
-            ButtonFunction::MakeCC => { 3 },
             ButtonFunction::PlaceBarracks => { 4 },
             ButtonFunction::PlaceCommandCentre => { 3 },
-            ButtonFunction::PlaceConstruction => { 6 },
+            ButtonFunction::PlaceRefinery => { 14 },
+            ButtonFunction::PlaceSupplyDepot => { 15 },
+            ButtonFunction::CancelConstruction => { 16 },
+            ButtonFunction::LiftOff => { 17 },
+            ButtonFunction::Land => { 18 },
+            ButtonFunction::Stop => { 7 },
+            ButtonFunction::MakeMarine => { 8 },
+            ButtonFunction::MakeWraith => { 9 },
+            ButtonFunction::Ability(ability) => { ability.get_texture_index() },
             _ => { unimplemented!() }
         }
     }
+
+    //Paid when the button is pressed, buildings once their ghost is placed
+    pub fn get_cost<'f>(&'f self) -> Cost {
+        match self {
+            ButtonFunction::MakeWorker => { UnitType::Worker.get_cost() },
+            ButtonFunction::MakeMarine => { UnitType::Marine.get_cost() },
+            ButtonFunction::MakeWraith => { UnitType::Wraith.get_cost() },
+            ButtonFunction::PlaceBarracks => { BuildingType::Barracks.get_cost() },
+            ButtonFunction::PlaceCommandCentre => { BuildingType::CommandCentre.get_cost() },
+            ButtonFunction::PlaceRefinery => { BuildingType::Refinery.get_cost() },
+            ButtonFunction::PlaceSupplyDepot => { BuildingType::SupplyDepot.get_cost() },
+            _ => { Cost::default() }
+        }
+    }
+
+    //Supply taken up from the moment the unit is queued
+    pub fn get_supply<'f>(&'f self) -> i32 {
+        match self {
+            ButtonFunction::MakeWorker => { UnitType::Worker.get_supply() },
+            ButtonFunction::MakeMarine => { UnitType::Marine.get_supply() },
+            ButtonFunction::MakeWraith => { UnitType::Wraith.get_supply() },
+            _ => { 0 }
+        }
+    }
 }
@@ -1,37 +1,503 @@
+use sdl2::rect::{Point, Rect};
 use sdl2::render::WindowCanvas;
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, Display, EnumString};
 
-use crate::sprite::TextureManager;
-use crate::general::{Selection, Selectable, Renderable};
-use crate::ui::Button;
+use std::time::Duration;
+use std::collections::{VecDeque, HashMap};
 
-#[derive(Clone, Copy)]
-pub struct Unit {
+use crate::sprite::{Sprite, TextureManager, TextureType};
+use crate::general::{self, Collidable, Faction, Selection, Selectable, Renderable};
+use crate::ui::{Button, ButtonFunction, UiElement};
+use crate::pathfinding::{self, PathGrid};
+use crate::flowfield::FlowField;
+use crate::steering::Body;
+use crate::combat::{self, Weapon, Target, Shot};
+use crate::ability::{self, AbilityKind, Caster, Cast, CastTarget, CastError};
+use crate::world::World;
+use crate::gather::Gather;
+use crate::resources::{ResourceField, Cost};
+use crate::stats;
+use crate::cards;
 
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Unit {
+    pub id: u32,
+    pub sprite: Sprite,
+    pub team: i32,
+    pub unit_type: UnitType,
+    pub faction: Faction,
+    pub collider_type: Collidable,
+    pub collider: Rect,
+    pub position: (f32, f32),
+    pub move_target: Option<Point>,
+    pub destination: Option<Point>, // Last move target, kept after arriving
+    pub waypoints: VecDeque<Point>,
+    pub flow_field: Option<u32>, // Id in Player::flow_fields
+    pub arrive_radius: f32,
+    pub speed: f32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub armor: i32,
+    pub weapon: Option<Weapon>,
+    pub attack_target: Option<u32>, // Id of the entity ordered to attack
+    pub caster: Caster,
+    pub gather: Option<Gather>,
+    pub construct: Option<u32>, // Id of the building site it's ordered to build
+    pub buttons: Vec<[Option<Button>; 16]>,
+    pub button_panel_index: usize,
 }
 
-#[allow(dead_code, unused_variables)]
 impl Unit {
+    //Location is the centre of the unit
+    pub fn new<'f>(location: Point, unit_type: UnitType, faction: Faction, team: i32,
+            bottom_right_ui: Vec<UiElement>, atlas: &'f TextureManager) -> Unit {
+        let collider = Rect::from_center(location, unit_type.get_w(), unit_type.get_h());
+
+        let mut new_unit = Unit {
+            id: general::next_entity_id(),
+            team,
+            unit_type,
+            faction,
+            collider_type: unit_type.get_collider_type(),
+            collider,
+            position: (location.x as f32, location.y as f32),
+            move_target: None,
+            destination: None,
+            waypoints: VecDeque::new(),
+            flow_field: None,
+            arrive_radius: 0.0,
+            speed: unit_type.get_speed(),
+            hp: unit_type.get_max_hp(),
+            max_hp: unit_type.get_max_hp(),
+            armor: unit_type.get_armor(),
+            weapon: unit_type.get_weapon(),
+            attack_target: None,
+            caster: Caster::new(unit_type.get_max_energy() / 4.0, unit_type.get_max_energy()),
+            gather: None,
+            construct: None,
+            buttons: vec![],
+            button_panel_index: 0,
+            sprite: Sprite::new(collider, TextureType::Unit { faction, u_type: unit_type },
+                atlas),
+        };
+
+        Unit::init_buttons(&mut new_unit, bottom_right_ui, atlas);
+
+        return new_unit;
+    }
+
+    fn init_buttons<'f>(unit: &'f mut Unit, bottom_right_ui: Vec<UiElement>,
+            atlas: &'f TextureManager) {
+        unit.buttons = general::gen_panels(atlas, &bottom_right_ui, cards::unit(unit.unit_type));
+    }
+
+    pub fn has_ability<'f>(&'f self, ability: AbilityKind) -> bool {
+        self.buttons.iter().flatten().flatten()
+            .any(|button| button.btn_function == ButtonFunction::Ability(ability))
+    }
+
+    pub fn execute_fn<'f>(&'f mut self, function: ButtonFunction) {
+        match function {
+            ButtonFunction::Stop => {
+                self.stop();
+            },
+            _ => {}
+        }
+    }
+
+    pub fn set_button_panel<'f>(&'f mut self, index: usize) {
+        self.button_panel_index = index;
+    }
+
+    pub fn get_location<'f>(&'f self) -> Point {
+        Point::new(self.position.0.round() as i32, self.position.1.round() as i32)
+    }
+
+    pub fn set_location<'f>(&'f mut self, position: (f32, f32)) {
+        self.position = position;
+        self.collider.center_on(self.get_location());
+        self.sprite.set_loc_rect(self.collider);
+    }
+
+    //Size of the unit in 25px grid cells
+    pub fn footprint<'f>(&'f self) -> usize {
+        ((self.collider.w.max(self.collider.h) + 24) / 25) as usize
+    }
+
+    //Top left grid cell of the footprint when centred on location
+    pub fn footprint_cell<'f, G: PathGrid>(&'f self, location: Point, grid: &'f G)
+            -> (usize, usize) {
+        let half = self.footprint() as i32 * 25 / 2;
+        let max_x = grid.width().saturating_sub(self.footprint()) as i32;
+        let max_y = grid.height().saturating_sub(self.footprint()) as i32;
+
+        (((location.x - half) / 25).clamp(0, max_x) as usize,
+            ((location.y - half) / 25).clamp(0, max_y) as usize)
+    }
+
+    pub fn is_air<'f>(&'f self) -> bool {
+        self.collider_type.is_air()
+    }
+
+    //Moves along an A* path around occupied cells, flyers go straight there
+    pub fn move_to<'f, G: PathGrid>(&'f mut self, target: Point, grid: &'f G) {
+        if self.is_air() {
+            self.arrive();
+            self.move_target = Some(target);
+            self.destination = Some(target);
+            self.waypoints.push_back(target);
+            return;
+        }
+
+        let start = self.footprint_cell(self.get_location(), grid);
+        let goal = self.footprint_cell(target, grid);
+        let half = self.footprint() as i32 * 25 / 2;
+
+        self.arrive();
+        self.move_target = Some(target);
+        self.destination = Some(target);
+
+        match pathfinding::find_path(grid, start, goal, self.footprint()) {
+            Some(path) => {
+                let reached_goal = path.last().map_or(start == goal, |last| *last == goal);
+                self.waypoints = pathfinding::to_waypoints(&path, start).iter()
+                    .map(|cell| Point::new(cell.0 as i32 * 25 + half, cell.1 as i32 * 25 + half))
+                    .collect();
+
+                if reached_goal {
+                    self.waypoints.pop_back();
+                    self.waypoints.push_back(target);
+                }
+            },
+            None => { // Stuck inside something, walk straight out
+                self.waypoints.push_back(target);
+            }
+        }
+    }
+
+    //Part of a group move, stops once within arrive_radius of target
+    pub fn follow_flow_field<'f>(&'f mut self, id: u32, target: Point, arrive_radius: f32) {
+        self.arrive();
+        self.move_target = Some(target);
+        self.destination = Some(target);
+        self.flow_field = Some(id);
+        self.arrive_radius = arrive_radius;
+    }
+
+    pub fn stop<'f>(&'f mut self) {
+        self.arrive();
+        self.destination = None;
+        self.attack_target = None;
+        self.caster.pending = None;
+        self.gather = None;
+        self.construct = None;
+    }
+
+    //Walks to the site and builds it, see Player::update_construction
+    pub fn build<'f>(&'f mut self, site: u32) {
+        self.stop();
+        self.construct = Some(site);
+    }
+
+    //Starts the mine and carry loop, see gather::step
+    pub fn gather_from<'f>(&'f mut self, field: &'f ResourceField) {
+        self.stop();
+        self.gather = Some(Gather::new(field));
+    }
+
+    //Walks into range of the pending cast's target, returns the cast once it's paid for
+    //or why it couldn't be
+    pub fn update_cast<'f, G: PathGrid>(&'f mut self, targets: &'f [Target], grid: &'f G)
+            -> Option<Result<Cast, CastError>> {
+        let cast = self.caster.pending?;
+        let target_area = match cast.target {
+            CastTarget::None => Some(self.collider),
+            CastTarget::Entity(id) => targets.iter()
+                .find(|target| target.id == id)
+                .map(|target| target.collider),
+            CastTarget::Point(point) => Some(Rect::from_center(point, 1, 1)),
+        };
+        let target_area = match target_area {
+            Some(area) => area,
+            None => { // Target died
+                self.caster.pending = None;
+                return None;
+            }
+        };
+
+        if !ability::in_range(cast.ability, self.collider, target_area) {
+            if self.destination != Some(target_area.center()) {
+                self.move_to(target_area.center(), grid);
+            } else if !self.is_moving() { // Got as close as the path allows
+                self.stop();
+            }
+            return None;
+        }
+
+        self.caster.pending = None;
+        if self.is_moving() {
+            self.arrive();
+        }
+
+        Some(self.caster.spend(cast.ability).map(|_| cast))
+    }
+
+    //Chases the entity with this id until it's in range, see update_attack
+    pub fn attack<'f>(&'f mut self, id: u32) {
+        self.stop();
+        self.attack_target = Some(id);
+    }
+
+    //Fires at the ordered target, or at the closest enemy in range while idle.
+    //Chases an ordered target that's out of range.
+    pub fn update_attack<'f, G: PathGrid>(&'f mut self, targets: &'f [Target], grid: &'f G)
+            -> Option<Shot> {
+        let weapon = self.weapon?;
+
+        let target = match self.attack_target {
+            Some(id) => {
+                match targets.iter().find(|target| target.id == id) {
+                    Some(target) if weapon.can_target(target.collider_type) => *target,
+                    _ => { // Dead or out of reach
+                        self.stop();
+                        return None;
+                    }
+                }
+            },
+            None if !self.is_moving() => {
+                combat::nearest_in_range(&weapon, self.collider, self.team, targets)?
+            },
+            None => { return None; }
+        };
+
+        if !weapon.in_range(self.collider, target.collider) {
+            let target_centre = target.collider.center();
+            let target_moved = match self.destination {
+                Some(destination) => {
+                    (destination.x - target_centre.x).abs() > 25 ||
+                        (destination.y - target_centre.y).abs() > 25
+                },
+                None => { true }
+            };
+
+            if target_moved {
+                self.move_to(target_centre, grid);
+            } else if !self.is_moving() { // Got as close as the path allows
+                self.stop();
+            }
+            return None;
+        }
+
+        if self.is_moving() {
+            self.arrive();
+        }
+
+        let damage = self.weapon.as_mut()?.fire()?;
+        Some(Shot::new(&weapon, damage, self.id, self.team, self.get_location(), target))
+    }
+
+    //Ends the current move but remembers where it was going
+    pub fn arrive<'f>(&'f mut self) {
+        self.move_target = None;
+        self.waypoints.clear();
+        self.flow_field = None;
+    }
+
+    pub fn is_moving<'f>(&'f self) -> bool {
+        self.move_target.is_some()
+    }
+
+    pub fn get_body<'f>(&'f self) -> Body {
+        Body {
+            position: self.position,
+            radius: self.collider.w.max(self.collider.h) as f32 / 2.0,
+            collider_type: self.collider_type,
+            moving: self.is_moving(),
+            team: self.team,
+            group: self.flow_field,
+            destination: self.destination.map(|point| (point.x as f32, point.y as f32)),
+        }
+    }
+
+    //Moves by offset unless that would put the unit on a blocked cell, sliding
+    //along whichever axis is still free
+    pub fn nudge<'f>(&'f mut self, offset: (f32, f32), game_map: &'f World) {
+        if offset.0 == 0.0 && offset.1 == 0.0 {
+            return;
+        }
+
+        let blocked = |unit: &Unit, position: (f32, f32)| {
+            unit.collider_type.collides_with(Collidable::World) && game_map.area_blocked(
+                Rect::from_center(Point::new(position.0.round() as i32,
+                    position.1.round() as i32), unit.collider.width(), unit.collider.height()))
+        };
+
+        let candidates = [
+            (self.position.0 + offset.0, self.position.1 + offset.1),
+            (self.position.0 + offset.0, self.position.1),
+            (self.position.0, self.position.1 + offset.1),
+        ];
+        if let Some(position) = candidates.iter().find(|position| !blocked(self, **position)) {
+            self.set_location(*position);
+        }
+    }
+
+    //Picks the next cell to walk to from the shared flow field
+    fn steer_by_flow_field<'f, G: PathGrid>(&'f mut self,
+            flow_fields: &'f HashMap<u32, FlowField>, grid: &'f G) {
+        let target = match self.move_target {
+            Some(target) => target,
+            None => { return; }
+        };
+        let field = match self.flow_field.and_then(|id| flow_fields.get(&id)) {
+            Some(field) => field,
+            None => {
+                self.stop();
+                return;
+            }
+        };
+
+        let dx = target.x as f32 - self.position.0;
+        let dy = target.y as f32 - self.position.1;
+        if (dx * dx + dy * dy).sqrt() <= self.arrive_radius {
+            self.arrive();
+            return;
+        }
 
+        let cell = self.footprint_cell(self.get_location(), grid);
+        let half = self.footprint() as i32 * 25 / 2;
+        self.waypoints.clear();
+
+        match field.direction_at(cell) {
+            Some((step_x, step_y)) => {
+                self.waypoints.push_back(Point::new(
+                    (cell.0 as i32 + step_x) * 25 + half,
+                    (cell.1 as i32 + step_y) * 25 + half));
+            },
+            None if cell == field.goal => {
+                self.waypoints.push_back(target);
+            },
+            None => { //Off the field or cut off inside it, find a way on our own
+                self.move_to(target, grid);
+            }
+        }
+    }
+
+    //Steps along the waypoints, called once per frame
+    pub fn update<'f, G: PathGrid>(&'f mut self, flow_fields: &'f HashMap<u32, FlowField>,
+            grid: &'f G) {
+        if self.flow_field.is_some() {
+            self.steer_by_flow_field(flow_fields, grid);
+        }
+
+        let mut step = self.speed;
+
+        while let Some(waypoint) = self.waypoints.front().copied() {
+            let dx = waypoint.x as f32 - self.position.0;
+            let dy = waypoint.y as f32 - self.position.1;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            if distance <= step {
+                self.set_location((waypoint.x as f32, waypoint.y as f32));
+                self.waypoints.pop_front();
+                step -= distance;
+            } else {
+                self.set_location((self.position.0 + dx / distance * step,
+                    self.position.1 + dy / distance * step));
+                break;
+            }
+        }
+
+        if self.waypoints.is_empty() && self.flow_field.is_none() {
+            self.move_target = None;
+        }
+    }
 }
 
-#[allow(dead_code, unused_variables)]
 impl Renderable for Unit {
     fn render<'f>(&'f self, tx_mgr: &'f TextureManager, canvas: &'f mut WindowCanvas) {
-        unimplemented!()
+        self.sprite.render(tx_mgr, canvas);
     }
 
-    fn get_loc_rect<'f>(&'f self) -> sdl2::rect::Rect {
-        unimplemented!()
+    fn get_loc_rect<'f>(&'f self) -> Rect {
+        self.sprite.get_loc_rect()
     }
 }
 
-#[allow(dead_code, unused_variables)]
 impl Selectable for Unit {
     fn get_selection<'f>(&'f self, index: usize) -> Selection {
-        unimplemented!()
+        Selection::Unit(index)
     }
 
     fn get_buttons<'f>(&'f self) -> &[Option<Button>; 16] {
-        unimplemented!()
+        &self.buttons[self.button_panel_index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, EnumIter, Display, EnumString)]
+pub enum UnitType {
+    Worker,
+    Marine,
+    Wraith,
+}
+
+impl UnitType {
+    pub fn get_all_variants() -> Vec<UnitType> {
+        UnitType::iter().collect()
+    }
+
+    pub fn can_build<'f>(&'f self) -> bool {
+        matches!(self, UnitType::Worker)
+    }
+
+    pub fn can_gather<'f>(&'f self) -> bool {
+        matches!(self, UnitType::Worker)
+    }
+
+    pub fn get_w<'f>(&'f self) -> u32 {
+        stats::unit(*self).w
+    }
+
+    pub fn get_h<'f>(&'f self) -> u32 {
+        stats::unit(*self).h
+    }
+
+    //Pixels per frame
+    pub fn get_speed<'f>(&'f self) -> f32 {
+        stats::unit(*self).speed
+    }
+
+    pub fn get_build_time<'f>(&'f self) -> Duration {
+        stats::unit(*self).build_time
+    }
+
+    pub fn get_cost<'f>(&'f self) -> Cost {
+        stats::unit(*self).cost
+    }
+
+    pub fn get_supply<'f>(&'f self) -> i32 {
+        stats::unit(*self).supply
+    }
+
+    pub fn get_max_hp<'f>(&'f self) -> i32 {
+        stats::unit(*self).max_hp
+    }
+
+    pub fn get_collider_type<'f>(&'f self) -> Collidable {
+        stats::unit(*self).collider_type
+    }
+
+    pub fn get_max_energy<'f>(&'f self) -> f32 {
+        stats::unit(*self).max_energy
+    }
+
+    pub fn get_armor<'f>(&'f self) -> i32 {
+        stats::unit(*self).armor
+    }
+
+    pub fn get_weapon<'f>(&'f self) -> Option<Weapon> {
+        stats::unit(*self).weapon
     }
 }
@@ -1,21 +1,61 @@
 use sdl2::render::WindowCanvas;
 use sdl2::rect::{Rect, Point};
 
+use std::path::Path;
+
 use crate::sprite::{TextureType, TextureManager};
 use crate::sprite::Sprite;
 use crate::general::{Collidable, Renderable};
+use crate::map::{MapFile, MapObject, MapResource, MapError, TILE_SIZE, PLACEABLE_TILES};
+use crate::pathfinding::{PathGrid, CellRegion};
+use crate::resources::{ResourceField, ResourceKind};
+use crate::building::PlacementError;
 
 //Represents current world or map, also used as camera boundary
 pub struct World {
+    pub name: String,
     pub world_sprites: Vec<Vec<Sprite>>,
     pub world_encode: Vec<Vec<i32>>,
     pub grid: Vec<Vec<Cell>>,
+    pub start_locations: Vec<Point>,
+    pub objects: Vec<WorldObject>,
+    pub resource_fields: Vec<ResourceField>,
+    pub occupancy_changes: Vec<CellRegion>, // Cleared every frame after units update
 }
 
 impl World {
+    pub fn load_from_file<'f>(path: &'f Path, atlas: &'f TextureManager)
+            -> Result<World, MapError> {
+        let map = MapFile::load(path)?;
+        Ok(World::from_map(&map, atlas))
+    }
+
+    pub fn from_map<'f>(map: &'f MapFile, atlas: &'f TextureManager) -> World {
+        let mut new_world = World::new(map.tiles.to_owned(), atlas);
+        new_world.name = map.name.to_owned();
+        new_world.start_locations = map.start_locations.to_owned();
+
+        for object in map.objects.iter() {
+            new_world.add_object(WorldObject::new(object.t_type, object.collider,
+                object.collider_type, atlas));
+        }
+
+        for resource in map.resources.iter() {
+            new_world.add_resource_field(ResourceField::new(resource.kind, resource.location,
+                resource.amount, atlas));
+        }
+
+        new_world
+    }
+
     pub fn new<'f>(world_encode: Vec<Vec<i32>>, atlas: &'f TextureManager) -> World {
         let mut new_world = World{
+            name: String::new(),
             world_encode,
+            start_locations: vec![],
+            objects: vec![],
+            resource_fields: vec![],
+            occupancy_changes: vec![],
             grid: {
                 let mut new_grid: Vec<Vec<Cell>> = vec![vec![]];
                 new_grid.pop();
@@ -67,6 +107,309 @@ impl World {
         }
     }
 
+    pub fn to_map<'f>(&'f self) -> MapFile {
+        MapFile {
+            name: self.name.to_owned(),
+            width: self.world_encode.len(),
+            height: self.world_encode[0].len(),
+            tiles: self.world_encode.to_owned(),
+            start_locations: self.start_locations.to_owned(),
+            objects: self.objects.iter()
+                .map(|object| MapObject {
+                    t_type: object.sprite.t_type,
+                    collider: object.collider,
+                    collider_type: object.collider_type,
+                })
+                .collect(),
+            resources: self.resource_fields.iter()
+                .map(|field| MapResource {
+                    kind: field.kind,
+                    location: field.collider.top_left(),
+                    amount: field.amount,
+                })
+                .collect(),
+        }
+    }
+
+    //Swaps the tile at the given world position (in pixels)
+    pub fn set_tile<'f>(&'f mut self, location: Point, tile_index: i32,
+            atlas: &'f TextureManager) {
+        if location.x < 0 || location.y < 0 {
+            return;
+        }
+
+        let x = (location.x / TILE_SIZE) as usize;
+        let y = (location.y / TILE_SIZE) as usize;
+        if x >= self.world_encode.len() || y >= self.world_encode[x].len() ||
+                self.world_encode[x][y] == tile_index {
+            return;
+        }
+
+        self.world_encode[x][y] = tile_index;
+        self.world_sprites[x][y] = Sprite::new(self.world_sprites[x][y].loc_rect,
+            TextureType::World { tile_index: tile_index as usize }, atlas);
+    }
+
+    //Removes the topmost object at location, buildings are the colliders of the
+    //buildings standing on the grid since the world doesn't keep track of them
+    pub fn remove_object_at<'f>(&'f mut self, location: Point, buildings: &'f [Rect]) -> bool {
+        let found = self.objects.iter()
+            .rposition(|object| object.collider.contains_point(location));
+
+        match found {
+            Some(index) => {
+                let removed = self.objects.remove(index);
+                if removed.blocks_ground() {
+                    self.set_occupied(removed.collider, false);
+                    self.reoccupy(removed.collider, buildings);
+                }
+                true
+            },
+            None => { false }
+        }
+    }
+
+    //Occupies the cells under area (in pixels) again for every grid holder still on them
+    fn reoccupy<'f>(&'f mut self, area: Rect, buildings: &'f [Rect]) {
+        let first_x = area.x.div_euclid(25) * 25;
+        let first_y = area.y.div_euclid(25) * 25;
+        let cells = Rect::new(first_x, first_y,
+            ((area.right() + 24).div_euclid(25) * 25 - first_x) as u32,
+            ((area.bottom() + 24).div_euclid(25) * 25 - first_y) as u32);
+
+        let holders: Vec<Rect> = self.objects.iter()
+            .filter(|object| object.blocks_ground())
+            .map(|object| object.collider)
+            .chain(self.resource_fields.iter().map(|field| field.collider))
+            .chain(buildings.iter().copied())
+            .filter(|holder| holder.has_intersection(cells))
+            .collect();
+        holders.iter().for_each(|holder| self.set_occupied(*holder, true));
+    }
+
+    pub fn add_object<'f>(&'f mut self, object: WorldObject) {
+        if object.blocks_ground() {
+            self.set_occupied(object.collider, true);
+        }
+        self.objects.push(object);
+    }
+
+    pub fn add_resource_field<'f>(&'f mut self, field: ResourceField) {
+        self.set_occupied(field.collider, true);
+        self.resource_fields.push(field);
+    }
+
+    //Removes the field and frees its cells, used when it runs dry
+    pub fn remove_resource_field<'f>(&'f mut self, id: u32) {
+        if let Some(index) = self.resource_fields.iter().position(|field| field.id == id) {
+            let removed = self.resource_fields.remove(index);
+            self.set_occupied(removed.collider, false);
+        }
+    }
+
+    pub fn remove_resource_field_at<'f>(&'f mut self, location: Point) -> bool {
+        match self.resource_at(location) {
+            Some(id) => {
+                self.remove_resource_field(id);
+                true
+            },
+            None => { false }
+        }
+    }
+
+    pub fn resource_field<'f>(&'f self, id: u32) -> Option<&'f ResourceField> {
+        self.resource_fields.iter().find(|field| field.id == id)
+    }
+
+    pub fn resource_field_mut<'f>(&'f mut self, id: u32) -> Option<&'f mut ResourceField> {
+        self.resource_fields.iter_mut().find(|field| field.id == id)
+    }
+
+    //Id of the field under location (in pixels)
+    pub fn resource_at<'f>(&'f self, location: Point) -> Option<u32> {
+        self.resource_fields.iter()
+            .find(|field| field.collider.contains_point(location))
+            .map(|field| field.id)
+    }
+
+    //Geyser lying exactly under area that nothing is built on yet
+    pub fn free_geyser_at<'f>(&'f self, area: Rect) -> Option<u32> {
+        self.resource_fields.iter()
+            .find(|field| field.kind == ResourceKind::Gas && field.collider == area &&
+                field.extractor.is_none())
+            .map(|field| field.id)
+    }
+
+    //Marks the geyser under a refinery, None when the refinery is gone
+    pub fn set_extractor<'f>(&'f mut self, field_id: u32, building_id: Option<u32>) {
+        if let Some(field) = self.resource_field_mut(field_id) {
+            field.extractor = building_id;
+        }
+    }
+
+    //Closest field of kind nobody is mining, within range pixels of location
+    pub fn nearest_free_field<'f>(&'f self, kind: ResourceKind, location: Point, range: f32)
+            -> Option<u32> {
+        let distance = |field: &ResourceField| {
+            let centre = field.collider.center();
+            (((centre.x - location.x).pow(2) + (centre.y - location.y).pow(2)) as f32).sqrt()
+        };
+
+        self.resource_fields.iter()
+            .filter(|field| field.kind == kind && field.harvester.is_none() &&
+                !field.depleted() && distance(field) <= range)
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .map(|field| field.id)
+    }
+
+    //Occupies or frees every grid cell overlapped by area (in pixels)
+    pub fn set_occupied<'f>(&'f mut self, area: Rect, occupied: bool) {
+        let first_x = (area.x / 25).max(0) as usize;
+        let first_y = (area.y / 25).max(0) as usize;
+        let last_x = ((area.right() + 24) / 25).max(0) as usize;
+        let last_y = ((area.bottom() + 24) / 25).max(0) as usize;
+
+        if first_x < last_x.min(self.grid.len()) && first_y < last_y.min(self.grid[0].len()) {
+            self.occupancy_changes.push(CellRegion::new(first_x, first_y,
+                last_x.min(self.grid.len()) - first_x, last_y.min(self.grid[0].len()) - first_y));
+        }
+
+        let mut i = first_x;
+        while i < last_x.min(self.grid.len()) {
+            let mut j = first_y;
+            while j < last_y.min(self.grid[i].len()) {
+                if occupied {
+                    self.grid[i][j].occupy();
+                } else {
+                    self.grid[i][j].deoccupy();
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+    }
+
+    //Whether any grid cell under area (in pixels) is occupied or off the map
+    pub fn area_blocked<'f>(&'f self, area: Rect) -> bool {
+        if area.x < 0 || area.y < 0 {
+            return true;
+        }
+
+        let last_x = ((area.right() - 1) / 25) as usize;
+        let last_y = ((area.bottom() - 1) / 25) as usize;
+        if last_x >= self.grid.len() || last_y >= self.grid[0].len() {
+            return true;
+        }
+
+        let mut i = (area.x / 25) as usize;
+        while i <= last_x {
+            let mut j = (area.y / 25) as usize;
+            while j <= last_y {
+                if self.grid[i][j].occupied {
+                    return true;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+
+        false
+    }
+
+    //Why a building can't cover the 25px cell at x, y, blockers are the ground units around
+    pub fn check_cell<'f>(&'f self, x: usize, y: usize, blockers: &'f [Rect])
+            -> Result<(), PlacementError> {
+        let cell = self.grid[x][y].get_loc_rect();
+        if !PLACEABLE_TILES.contains(&self.world_encode[x / 2][y / 2]) ||
+                self.objects.iter().any(|object| object.blocks_ground() &&
+                    object.collider.has_intersection(cell)) ||
+                self.resource_fields.iter().any(|field| field.collider.has_intersection(cell)) {
+            return Err(PlacementError::Unbuildable);
+        }
+
+        //Whatever else holds the cell is a building
+        if self.grid[x][y].occupied {
+            return Err(PlacementError::OverlapsBuilding);
+        }
+
+        if blockers.iter().any(|blocker| blocker.has_intersection(cell)) {
+            return Err(PlacementError::BlockedByUnit);
+        }
+
+        Ok(())
+    }
+
+    //Whether a building fits on area (in pixels), when several cells are bad the
+    //problem listed first in PlacementError is reported
+    pub fn can_place<'f>(&'f self, area: Rect, blockers: &'f [Rect])
+            -> Result<(), PlacementError> {
+        if area.x < 0 || area.y < 0 || area.right() > self.grid.len() as i32 * 25 ||
+                area.bottom() > self.grid[0].len() as i32 * 25 {
+            return Err(PlacementError::OutOfBounds);
+        }
+
+        let first_y = (area.y / 25) as usize;
+        let last_y = ((area.bottom() - 1) / 25) as usize;
+        let worst = ((area.x / 25) as usize..=((area.right() - 1) / 25) as usize)
+            .flat_map(|x| (first_y..=last_y).map(move |y| (x, y)))
+            .filter_map(|(x, y)| self.check_cell(x, y, blockers).err())
+            .min();
+
+        match worst {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    //Centre of the closest free 25px cell around area, searched ring by ring
+    //starting below it, blocked holds areas taken by things that aren't on the grid
+    pub fn find_free_cell_near<'f>(&'f self, area: Rect, blocked: &'f [Rect])
+            -> Option<Point> {
+        let first_x = area.x / 25;
+        let first_y = area.y / 25;
+        let last_x = (area.right() - 1) / 25;
+        let last_y = (area.bottom() - 1) / 25;
+        let max_ring = self.grid.len().max(self.grid[0].len()) as i32;
+
+        let mut ring: i32 = 1;
+        while ring < max_ring {
+            let mut ring_cells: Vec<(i32, i32)> = vec![];
+            let mut i = first_x - ring;
+            while i <= last_x + ring {
+                ring_cells.push((i, last_y + ring));
+                i += 1;
+            }
+            let mut j = last_y + ring - 1;
+            while j >= first_y - ring {
+                ring_cells.push((first_x - ring, j));
+                ring_cells.push((last_x + ring, j));
+                j -= 1;
+            }
+            i = first_x - ring + 1;
+            while i < last_x + ring {
+                ring_cells.push((i, first_y - ring));
+                i += 1;
+            }
+
+            for (x, y) in ring_cells {
+                if x < 0 || y < 0 || x as usize >= self.grid.len() ||
+                        y as usize >= self.grid[x as usize].len() {
+                    continue;
+                }
+
+                let cell = self.grid[x as usize][y as usize];
+                if !cell.occupied && !blocked.iter()
+                        .any(|rect| rect.has_intersection(cell.get_loc_rect())) {
+                    return Some(cell.get_loc_rect().center());
+                }
+            }
+
+            ring += 1;
+        }
+
+        None
+    }
+
     pub fn render<'f>(&'f self, canvas: &'f mut WindowCanvas, mut viewport: Rect,
             show_grid: bool, tx_mgr: &'f TextureManager) {
         
@@ -87,6 +430,20 @@ impl World {
     }
 }
 
+impl PathGrid for World {
+    fn width<'f>(&'f self) -> usize {
+        self.grid.len()
+    }
+
+    fn height<'f>(&'f self) -> usize {
+        self.grid[0].len()
+    }
+
+    fn is_blocked<'f>(&'f self, x: usize, y: usize) -> bool {
+        self.grid[x][y].occupied
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Cell {
     pub sprite: Sprite,
@@ -108,7 +465,7 @@ impl Cell {
         self.sprite.texture_rect.x = 64;
     }
 
-    pub fn _deoccupy<'f>(&'f mut self) {
+    pub fn deoccupy<'f>(&'f mut self) {
         self.occupied = false;
         if self.highlighted {
             self.sprite.texture_rect.x = 128;
@@ -118,18 +475,15 @@ impl Cell {
 
     }
     
-    pub fn highlight<'f>(&'f mut self) {
+    //Green under a ghost where the building can go, red where it can't
+    pub fn highlight<'f>(&'f mut self, valid: bool) {
         self.highlighted = true;
-        if !self.occupied {
-            self.sprite.texture_rect.x = 128;
-        }
+        self.sprite.texture_rect.x = if valid { 128 } else { 64 };
     }
 
     pub fn dehighlight<'f>(&'f mut self) {
         self.highlighted = false;
-        if !self.occupied {
-            self.sprite.texture_rect.x = 0;
-        }
+        self.sprite.texture_rect.x = if self.occupied { 64 } else { 0 };
     }
 }
 
@@ -143,11 +497,11 @@ impl Renderable for Cell {
     }
 }
 
-#[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub struct WorldObject {
-    sprite: Sprite,
-    collider: Rect,
-    collider_type: Collidable,
+    pub sprite: Sprite,
+    pub collider: Rect,
+    pub collider_type: Collidable,
 }
 
 #[allow(dead_code)]
@@ -160,6 +514,10 @@ impl WorldObject {
             collider_type,
         }
     }
+
+    pub fn blocks_ground<'f>(&'f self) -> bool {
+        self.collider_type.collides_with(Collidable::GroundCollidable)
+    }
 }
 
 impl Renderable for WorldObject {
@@ -171,3 +529,151 @@ impl Renderable for WorldObject {
         self.collider
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{GRASS_TILE, DIRT_TILE};
+
+    const UNBUILDABLE_TILE: i32 = 0;
+
+    fn sprite(loc_rect: Rect) -> Sprite {
+        Sprite {
+            t_type: TextureType::World { tile_index: 0 },
+            loc_rect,
+            texture_rect: Rect::new(0, 0, 64, 64),
+        }
+    }
+
+    //4x4 tiles (8x8 cells), grass on the left half, dirt on the right and the top left
+    //tile unbuildable. A ground object sits on tile (0, 3), a mineral field on (3, 3)
+    //and a building holds the cells of tile (3, 0)
+    fn small_world() -> World {
+        let mut world_encode = vec![vec![GRASS_TILE; 4], vec![GRASS_TILE; 4],
+            vec![DIRT_TILE; 4], vec![DIRT_TILE; 4]];
+        world_encode[0][0] = UNBUILDABLE_TILE;
+
+        let mut world = World {
+            name: String::new(),
+            world_sprites: vec![],
+            world_encode,
+            grid: (0..8).map(|x| (0..8).map(|y| Cell {
+                    sprite: sprite(Rect::new(x * 25, y * 25, 25, 25)),
+                    occupied: false,
+                    highlighted: false,
+                }).collect())
+                .collect(),
+            start_locations: vec![],
+            objects: vec![],
+            resource_fields: vec![],
+            occupancy_changes: vec![],
+        };
+
+        let object = Rect::new(0, 150, 50, 50);
+        world.add_object(WorldObject {
+            sprite: sprite(object),
+            collider: object,
+            collider_type: Collidable::GroundCollidable,
+        });
+        let field = Rect::new(150, 150, 50, 50);
+        world.add_resource_field(ResourceField {
+            id: 1,
+            kind: ResourceKind::Minerals,
+            collider: field,
+            amount: 100,
+            harvester: None,
+            extractor: None,
+            sprite: sprite(field),
+        });
+        world.set_occupied(Rect::new(150, 0, 50, 50), true);
+
+        world
+    }
+
+    #[test]
+    fn free_site() {
+        let world = small_world();
+
+        assert_eq!(world.can_place(Rect::new(50, 50, 100, 100), &[]), Ok(()));
+        assert_eq!(world.check_cell(5, 2, &[]), Ok(()));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let world = small_world();
+
+        assert_eq!(world.can_place(Rect::new(-25, 50, 50, 50), &[]),
+            Err(PlacementError::OutOfBounds));
+        assert_eq!(world.can_place(Rect::new(175, 50, 50, 50), &[]),
+            Err(PlacementError::OutOfBounds));
+        assert_eq!(world.can_place(Rect::new(50, 175, 50, 50), &[]),
+            Err(PlacementError::OutOfBounds));
+    }
+
+    #[test]
+    fn unbuildable() {
+        let world = small_world();
+
+        //Terrain, object and resource field
+        assert_eq!(world.check_cell(1, 1, &[]), Err(PlacementError::Unbuildable));
+        assert_eq!(world.check_cell(0, 7, &[]), Err(PlacementError::Unbuildable));
+        assert_eq!(world.check_cell(7, 7, &[]), Err(PlacementError::Unbuildable));
+        assert_eq!(world.can_place(Rect::new(25, 25, 50, 50), &[]),
+            Err(PlacementError::Unbuildable));
+    }
+
+    #[test]
+    fn objects_that_dont_block_ground() {
+        let mut world = small_world();
+        let object = Rect::new(100, 100, 50, 50);
+        world.add_object(WorldObject {
+            sprite: sprite(object),
+            collider: object,
+            collider_type: Collidable::AirCollidable,
+        });
+
+        assert_eq!(world.can_place(object, &[]), Ok(()));
+    }
+
+    #[test]
+    fn overlaps_building() {
+        let world = small_world();
+
+        assert_eq!(world.check_cell(6, 0, &[]), Err(PlacementError::OverlapsBuilding));
+        assert_eq!(world.can_place(Rect::new(125, 25, 50, 50), &[]),
+            Err(PlacementError::OverlapsBuilding));
+    }
+
+    #[test]
+    fn blocked_by_unit() {
+        let world = small_world();
+        let units = [Rect::new(110, 110, 10, 10)];
+
+        assert_eq!(world.check_cell(4, 4, &units), Err(PlacementError::BlockedByUnit));
+        assert_eq!(world.check_cell(5, 5, &units), Ok(()));
+        assert_eq!(world.can_place(Rect::new(100, 100, 50, 50), &units),
+            Err(PlacementError::BlockedByUnit));
+    }
+
+    #[test]
+    fn worst_reason_wins() {
+        let world = small_world();
+        let units = [Rect::new(60, 10, 10, 10), Rect::new(160, 60, 10, 10)];
+
+        //A cell with several problems reports the worst one
+        assert_eq!(world.check_cell(0, 6, &[Rect::new(0, 150, 10, 10)]),
+            Err(PlacementError::Unbuildable));
+        assert_eq!(world.check_cell(6, 0, &[Rect::new(150, 0, 10, 10)]),
+            Err(PlacementError::OverlapsBuilding));
+
+        //A site reports the worst problem among all of its cells
+        assert_eq!(world.can_place(Rect::new(0, 0, 200, 100), &units),
+            Err(PlacementError::Unbuildable));
+        assert_eq!(world.can_place(Rect::new(50, 0, 150, 100), &units),
+            Err(PlacementError::OverlapsBuilding));
+        assert_eq!(world.can_place(Rect::new(50, 0, 100, 100), &units),
+            Err(PlacementError::BlockedByUnit));
+        assert_eq!(world.can_place(Rect::new(-25, 0, 100, 100), &units),
+            Err(PlacementError::OutOfBounds));
+    }
+}